@@ -0,0 +1,113 @@
+// Runs a labeled corpus through retrieval and the generate pipeline and writes a JSON
+// report that can be diffed between runs. The backend comes from LLM_BACKEND as in
+// the app; with "fake", each case's `fake_replies` are played back instead.
+//
+//     cargo run --features eval --bin qpg-eval -- [--corpus eval/corpus.json] [--model NAME]
+//         [--env gnome] [--catalog PATH] [--output qpg-eval.json] [--rule-fallback]
+//
+// The rule-based fallback is off unless asked for, so model failures show up in the
+// report rather than being answered by the parser.
+
+use std::env;
+use std::fs;
+use std::sync::Arc;
+use qpg_application_lib::eval::{EvalCase, EvalOptions, Evaluator};
+use qpg_application_lib::llm::{self, fake::FakeBackend, LlmBackend};
+
+const DEFAULT_CORPUS: &str = include_str!("../../eval/corpus.json");
+const DEFAULT_CATALOG: &str = include_str!("../json_example.json");
+
+struct Args {
+    corpus: Option<String>,
+    model: Option<String>,
+    env: String,
+    catalog: Option<String>,
+    output: String,
+    rule_fallback: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        corpus: None,
+        model: None,
+        env: "gnome".to_string(),
+        catalog: None,
+        output: "qpg-eval.json".to_string(),
+        rule_fallback: false,
+    };
+    let mut raw = env::args().skip(1);
+    while let Some(flag) = raw.next() {
+        let mut value = || raw.next().ok_or(format!("{} needs a value", flag));
+        match flag.as_str() {
+            "--corpus" => args.corpus = Some(value()?),
+            "--model" => args.model = Some(value()?),
+            "--env" => args.env = value()?,
+            "--catalog" => args.catalog = Some(value()?),
+            "--output" => args.output = value()?,
+            "--rule-fallback" => args.rule_fallback = true,
+            other => return Err(format!("Unknown argument '{}'", other)),
+        }
+    }
+    Ok(args)
+}
+
+fn read_or(path: Option<&str>, default: &str) -> Result<String, String> {
+    match path {
+        Some(path) => fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e)),
+        None => Ok(default.to_string()),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    dotenv::dotenv().ok();
+    let args = parse_args()?;
+    let cases: Vec<EvalCase> = serde_json::from_str(&read_or(args.corpus.as_deref(), DEFAULT_CORPUS)?)
+        .map_err(|e| format!("Failed to parse the corpus: {}", e))?;
+
+    let fake = (*llm::LLM_BACKEND == "fake").then(|| Arc::new(FakeBackend::default()));
+    let backend: Arc<dyn LlmBackend> = match &fake {
+        Some(fake) => fake.clone(),
+        None => llm::from_config(),
+    };
+    let model = match (args.model, &fake) {
+        (Some(model), _) => model,
+        (None, Some(_)) => "fake".to_string(),
+        (None, None) => llm::DEFAULT_MODEL.clone(),
+    };
+
+    let evaluator = Evaluator::new(EvalOptions {
+        model,
+        env: args.env,
+        catalog: read_or(args.catalog.as_deref(), DEFAULT_CATALOG)?,
+        rule_fallback: args.rule_fallback,
+    }).await?;
+
+    let mut results = Vec::new();
+    for (index, case) in cases.iter().enumerate() {
+        if let Some(fake) = &fake {
+            fake.clear_replies();
+            for reply in &case.fake_replies {
+                fake.push_reply(llm::fake::FakeReply::text(reply));
+            }
+        }
+        results.push(evaluator.run_case(backend.as_ref(), index, case).await);
+    }
+
+    let report = evaluator.report(backend.name(), results);
+    let json = serde_json::to_string_pretty(&report).map_err(|e| format!("Failed to write the report: {}", e))?;
+    fs::write(&args.output, json + "\n").map_err(|e| format!("Failed to write {}: {}", args.output, e))?;
+
+    let summary = &report.summary;
+    println!();
+    println!("cases:              {}", summary.cases);
+    println!("top-1 retrieval:    {:.3}", summary.top1_accuracy);
+    println!("top-{} retrieval:    {:.3}", report.top_k, summary.top_k_accuracy);
+    println!("setting accuracy:   {:.3}", summary.setting_accuracy);
+    println!("value accuracy:     {}", summary.value_accuracy.map_or("-".to_string(), |v| format!("{:.3}", v)));
+    println!("parse failures:     {:.3}", summary.parse_failure_rate);
+    println!("errors:             {:.3}", summary.error_rate);
+    println!("generate p50/p95:   {:.1} / {:.1} ms", summary.generate_latency.p50_ms, summary.generate_latency.p95_ms);
+    println!("report written to {}", args.output);
+    Ok(())
+}
@@ -0,0 +1,242 @@
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use base64::prelude::*;
+use tauri::{AppHandle, Manager};
+use crate::encryption::{self, DecryptData};
+use crate::history::now_millis;
+use crate::llm::ChatMessage;
+use crate::models::{ChatRequest, ChatSession, ChatSummary, ExportFormat, GenerateResult, TranscriptEntry};
+
+const CHATS_DIR: &str = "chats";
+const KEY_FILE: &str = "chats.key";
+const TITLE_LENGTH: usize = 48;
+
+// Chats are encrypted with a key that never leaves this machine. It is created on
+// first use next to the chat files and readable only by the user.
+fn load_key(app_handle: &AppHandle) -> Result<Vec<u8>, String> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("No app data directory: {}", e))?;
+    let path = dir.join(KEY_FILE);
+
+    if let Some(key) = read_key(&path)? {
+        return Ok(key);
+    }
+
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let key = encryption::generate_key();
+    // Created with its final permissions, so the key is never readable by others.
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = match options.open(&path) {
+        Ok(file) => file,
+        // Another save got there first; use its key.
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            return read_key(&path)?.ok_or_else(|| format!("{} disappeared while being created", path.display()));
+        }
+        Err(e) => return Err(format!("Failed to create {}: {}", path.display(), e)),
+    };
+    if let Err(e) = file.write_all(&key).and_then(|_| file.sync_all()) {
+        let _ = fs::remove_file(&path);
+        return Err(format!("Failed to write {}: {}", path.display(), e));
+    }
+    println!("[chats] Created a new chat encryption key at {}", path.display());
+    Ok(key)
+}
+
+// Only a missing key means a new one is made. Any other failure is an error, since
+// a new key would leave the existing chats unreadable.
+fn read_key(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    match fs::read(path) {
+        Ok(key) if key.len() == 32 => Ok(Some(key)),
+        Ok(_) => Err(format!("{} is corrupt", path.display())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn chats_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join(CHATS_DIR))
+        .map_err(|e| format!("No app data directory: {}", e))
+}
+
+// Chat ids come from the frontend and may hold any characters, so file names are
+// the id in URL-safe base64.
+fn chat_path(app_handle: &AppHandle, chat_id: &str) -> Result<PathBuf, String> {
+    Ok(chats_dir(app_handle)?.join(format!("{}.json", BASE64_URL_SAFE_NO_PAD.encode(chat_id))))
+}
+
+pub fn load_chat(app_handle: &AppHandle, chat_id: &str) -> Result<ChatSession, String> {
+    find_chat(app_handle, chat_id)?.ok_or_else(|| format!("No chat with id '{}'", chat_id))
+}
+
+// None only when the chat has never been saved; a file that can't be read or
+// decrypted is an error.
+fn find_chat(app_handle: &AppHandle, chat_id: &str) -> Result<Option<ChatSession>, String> {
+    let path = chat_path(app_handle, chat_id)?;
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    decrypt_session(&load_key(app_handle)?, &contents).map(Some)
+}
+
+fn decrypt_session(key: &[u8], contents: &str) -> Result<ChatSession, String> {
+    let sealed: DecryptData = serde_json::from_str(contents)
+        .map_err(|e| format!("Unreadable chat file: {}", e))?;
+    let plaintext = encryption::decrypt_with_key(key, &sealed)?;
+    serde_json::from_str(&plaintext).map_err(|e| format!("Unreadable chat: {}", e))
+}
+
+pub fn save_chat(app_handle: &AppHandle, session: &ChatSession) -> Result<(), String> {
+    let key = load_key(app_handle)?;
+    let plaintext = serde_json::to_string(session).map_err(|e| format!("Failed to serialize chat: {}", e))?;
+    let sealed = encryption::encrypt_with_key(&key, &plaintext)?;
+
+    let path = chat_path(app_handle, &session.id)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let contents = serde_json::to_string(&sealed).map_err(|e| format!("Failed to serialize chat: {}", e))?;
+    write_atomically(&path, contents.as_bytes())
+}
+
+// Written next to the chat and renamed over it, so a crash mid-write leaves the old
+// chat in place rather than a truncated one.
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let temp = path.with_extension("json.tmp");
+    fs::write(&temp, contents)
+        .and_then(|_| fs::rename(&temp, path))
+        .map_err(|e| {
+            let _ = fs::remove_file(&temp);
+            format!("Failed to write {}: {}", path.display(), e)
+        })
+}
+
+pub fn delete_chat(app_handle: &AppHandle, chat_id: &str) -> Result<(), String> {
+    let path = chat_path(app_handle, chat_id)?;
+    fs::remove_file(&path).map_err(|_| format!("No chat with id '{}'", chat_id))
+}
+
+// Most recently used first. Files that can't be decrypted are skipped rather than
+// failing the whole list.
+pub fn list_chats(app_handle: &AppHandle) -> Result<Vec<ChatSummary>, String> {
+    let dir = chats_dir(app_handle)?;
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+    let key = load_key(app_handle)?;
+
+    let mut chats: Vec<ChatSummary> = entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| {
+            let contents = fs::read_to_string(entry.path()).ok()?;
+            match decrypt_session(&key, &contents) {
+                Ok(session) => Some(summarize(&session)),
+                Err(e) => {
+                    println!("[chats] Skipping {}: {}", entry.path().display(), e);
+                    None
+                }
+            }
+        })
+        .collect();
+    chats.sort_by_key(|chat| std::cmp::Reverse(chat.updated_at));
+    Ok(chats)
+}
+
+pub fn summarize(session: &ChatSession) -> ChatSummary {
+    ChatSummary {
+        id: session.id.clone(),
+        title: session.title.clone(),
+        model: session.model.clone(),
+        created_at: session.created_at,
+        updated_at: session.updated_at,
+        message_count: session.transcript.len(),
+    }
+}
+
+// Saves the exchange that just finished along with the model's full context for the
+// chat. Failures are logged, since losing the saved copy shouldn't fail the reply.
+pub fn record_exchange(app_handle: &AppHandle, history: &[ChatMessage], request: &ChatRequest, result: &GenerateResult) {
+    let now = now_millis();
+    // Saving over a chat that failed to load would lose it, so that exchange isn't saved.
+    let mut session = match find_chat(app_handle, &request.chat_id) {
+        Ok(Some(session)) => session,
+        Ok(None) => ChatSession {
+            id: request.chat_id.clone(),
+            title: default_title(&request.prompt),
+            model: request.model.clone(),
+            created_at: now,
+            updated_at: now,
+            transcript: Vec::new(),
+            messages: Vec::new(),
+        },
+        Err(e) => {
+            println!("[chats] Not saving chat {}, it could not be loaded: {}", request.chat_id, e);
+            return;
+        }
+    };
+
+    let reply = result.ollama_response.message.content.clone();
+    session.transcript.push(TranscriptEntry { sender: "user".to_string(), text: request.prompt.clone(), timestamp: now });
+    session.transcript.push(TranscriptEntry { sender: "bot".to_string(), text: reply, timestamp: now_millis() });
+    session.messages = history.to_vec();
+    session.model = request.model.clone();
+    session.updated_at = now_millis();
+
+    if let Err(e) = save_chat(app_handle, &session) {
+        println!("[chats] Failed to save chat {}: {}", request.chat_id, e);
+    }
+}
+
+fn default_title(prompt: &str) -> String {
+    let prompt = prompt.trim();
+    if prompt.chars().count() <= TITLE_LENGTH {
+        return prompt.to_string();
+    }
+    let title: String = prompt.chars().take(TITLE_LENGTH).collect();
+    format!("{}…", title.trim_end())
+}
+
+// A chat saved before a restart picks up where it left off instead of starting over.
+pub fn saved_history(app_handle: &AppHandle, chat_id: &str) -> Vec<ChatMessage> {
+    load_chat(app_handle, chat_id)
+        .map(|session| session.messages)
+        .unwrap_or_default()
+}
+
+// The export is the conversation as the user saw it; the model's context holds the
+// catalog snippets and system prompt, which aren't useful to share.
+pub fn export_chat(session: &ChatSession, format: ExportFormat) -> Result<String, String> {
+    match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&serde_json::json!({
+            "id": session.id,
+            "title": session.title,
+            "model": session.model,
+            "created_at": session.created_at,
+            "updated_at": session.updated_at,
+            "transcript": session.transcript,
+        }))
+        .map_err(|e| format!("Failed to export chat: {}", e)),
+        ExportFormat::Markdown => {
+            let mut out = format!("# {}\n\nModel: {}\n", session.title, session.model);
+            for entry in &session.transcript {
+                let speaker = if entry.sender == "user" { "You" } else { "Assistant" };
+                out.push_str(&format!("\n**{}:** {}\n", speaker, entry.text));
+            }
+            Ok(out)
+        }
+    }
+}
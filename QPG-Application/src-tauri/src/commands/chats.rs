@@ -0,0 +1,58 @@
+use tauri::{AppHandle, State};
+use crate::chats;
+use crate::history::now_millis;
+use crate::models::{ChatSession, ChatSummary, ExportFormat};
+use crate::state::ChatHistories;
+
+#[tauri::command]
+pub async fn list_chats(app_handle: AppHandle) -> Result<Vec<ChatSummary>, String> {
+    chats::list_chats(&app_handle)
+}
+
+// Loads a saved chat and puts its history back into the model's context. A chat
+// without a system prompt gets one with its next message.
+#[tauri::command]
+pub async fn open_chat(
+    chat_id: String,
+    app_handle: AppHandle,
+    histories: State<'_, ChatHistories>,
+) -> Result<ChatSession, String> {
+    let session = chats::load_chat(&app_handle, &chat_id)?;
+    histories.replace(&chat_id, session.messages.clone()).await;
+    Ok(session)
+}
+
+#[tauri::command]
+pub async fn rename_chat(chat_id: String, title: String, app_handle: AppHandle) -> Result<ChatSummary, String> {
+    let title = title.trim().to_string();
+    if title.is_empty() {
+        return Err("Chat title cannot be empty".to_string());
+    }
+
+    let mut session = chats::load_chat(&app_handle, &chat_id)?;
+    session.title = title;
+    session.updated_at = now_millis();
+    chats::save_chat(&app_handle, &session)?;
+    Ok(chats::summarize(&session))
+}
+
+#[tauri::command]
+pub async fn delete_chat(
+    chat_id: String,
+    app_handle: AppHandle,
+    histories: State<'_, ChatHistories>,
+) -> Result<(), String> {
+    chats::delete_chat(&app_handle, &chat_id)?;
+    histories.remove(&chat_id).await;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn export_chat(
+    chat_id: String,
+    format: Option<ExportFormat>,
+    app_handle: AppHandle,
+) -> Result<String, String> {
+    let session = chats::load_chat(&app_handle, &chat_id)?;
+    chats::export_chat(&session, format.unwrap_or_default())
+}
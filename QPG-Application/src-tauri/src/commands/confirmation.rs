@@ -0,0 +1,40 @@
+use tauri::{AppHandle, State};
+use crate::confirmation;
+use crate::models::PendingChange;
+use crate::state::{EncryptionClientInstance, GenerateState};
+
+#[tauri::command]
+pub async fn list_pending_changes(state: State<'_, GenerateState>) -> Result<Vec<PendingChange>, String> {
+    Ok(state.get_pending_changes().await)
+}
+
+#[tauri::command]
+pub async fn confirm_change(
+    id: String,
+    app_handle: AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<(), String> {
+    let pending = state
+        .take_pending_change(&id)
+        .await
+        .ok_or_else(|| format!("No pending change '{}'; it may already have been reverted", id))?;
+    println!("[confirmation] Change {} to '{}' confirmed", pending.id, pending.setting);
+
+    confirmation::keep_pending(&app_handle, encryption_instance, state, pending).await
+}
+
+#[tauri::command]
+pub async fn revert_change(
+    id: String,
+    app_handle: AppHandle,
+    state: State<'_, GenerateState>,
+) -> Result<(), String> {
+    let pending = state
+        .take_pending_change(&id)
+        .await
+        .ok_or_else(|| format!("No pending change '{}'; it may already have been reverted", id))?;
+    println!("[confirmation] Change {} to '{}' rejected, reverting", pending.id, pending.setting);
+
+    confirmation::revert_pending(&app_handle, &pending).await
+}
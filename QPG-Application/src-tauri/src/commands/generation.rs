@@ -1,182 +1,861 @@
-use tauri::{AppHandle, State};
-use crate::state::{OllamaInstance, ChatIDs, GenerateState, EncryptionClientInstance};
-use crate::models::{ChatRequest, GenerateResult, ModelResponse};
-use crate::preferences::{update_json_current_value, gather_valid_commands_for_env};
-use ollama_rs::generation::chat::{ChatMessage, MessageRole};
-use ollama_rs::generation::chat::request::ChatMessageRequest;
+use std::env;
+use std::time::Duration;
+use once_cell::sync::Lazy;
+use tauri::{AppHandle, Emitter, State};
+use crate::state::{LlmInstance, ChatHistories, GenerateState, EncryptionClientInstance};
+use crate::models::{AppConfig, ChangeSource, ChatRequest, DefaultValue, GenerateChunk, GenerateResult, ModelResponse, ModelStatus, PendingChange, ReplyKind, SettingAction, SettingMatch};
+use crate::tools::valid_choices;
+use crate::llm::{ChatMessage, LlmBackend, LlmError, LlmReply, LlmRequest, MessageRole, OnContent};
+use crate::locale::Language;
+use crate::{chats, confirmation, context, intent, model_manager, preferences, preview, profiles, prompts, retrieval, settings, tools};
+use crate::prompts::PromptVariables;
+use crate::intent::RulesMode;
+use crate::preferences::{update_json_current_value, gather_valid_commands_for_env, find_setting_for_command, parse_new_value};
+use schemars::gen::SchemaSettings;
 use serde_json::{Value};
 use tauri_plugin_shell::ShellExt;
 
+const MAX_REPAIR_ATTEMPTS: usize = 1;
+const NO_MATCH_HINT: &str = "No setting clearly matches the next message. If it follows up on the previous request, keep working with that setting. Otherwise don't guess: ask which setting the user means, offering two to four likely candidates as choices.";
+const REPAIR_PROMPT: &str = "Your last reply was not a valid JSON object. Reply again with only the JSON object, using the keys \"message\" and \"commands\".";
+
+pub static GENERATE_TIMEOUT_SECS: Lazy<u64> = Lazy::new(|| {
+    env::var("GENERATE_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(300)
+});
+
 pub async fn generate_impl(
     request: ChatRequest,
     encryption_instance: State<'_, EncryptionClientInstance>,
-    g_ollama: State<'_, OllamaInstance>,
-    seen_chats: State<'_, ChatIDs>,
+    llm: State<'_, LlmInstance>,
+    histories: State<'_, ChatHistories>,
     app_handle: AppHandle,
     state: State<'_, GenerateState>
 ) -> Result<GenerateResult, String> {
-    let username = state.get_username(&app_handle).await;
-    let platform_info = state.get_platform_info().await;
+    run_turn(
+        &request,
+        &encryption_instance,
+        llm.0.as_ref(),
+        &histories,
+        &app_handle,
+        &state,
+        &mut |_| {}
+    ).await
+}
 
-    if state.get_full_json().await.is_empty() {
-	println!("[generate] Full JSON empty; fetching preferences from server...");
-	
-	crate::preferences::fetch_preferences_impl(
-            &username,
-            &encryption_instance,
-            &platform_info,
-            &state
-	)
-	    .await
-	    .map_err(|err| format!("Failed to automatically fetch preferences: {}", err))?;
+// Streams the reply as it is generated, emitting the partial "message" text as
+// `generate-chunk` events.
+pub async fn generate_stream_impl(
+    request: ChatRequest,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    llm: State<'_, LlmInstance>,
+    histories: State<'_, ChatHistories>,
+    app_handle: AppHandle,
+    state: State<'_, GenerateState>
+) -> Result<GenerateResult, String> {
+    let mut last_message = String::new();
+    let mut on_content = |content: &str| emit_chunk(&app_handle, &request.chat_id, content, &mut last_message);
+    run_turn(
+        &request,
+        &encryption_instance,
+        llm.0.as_ref(),
+        &histories,
+        &app_handle,
+        &state,
+        &mut on_content
+    ).await
+}
+
+// Turns in the same chat wait for each other so its history stays in order, while
+// turns in different chats run side by side, up to MAX_CONCURRENT_GENERATIONS at a
+// time. The history is worked on as a copy and only written back when the turn
+// succeeds, so a cancelled or timed-out turn leaves its chat as it was.
+async fn run_turn(
+    request: &ChatRequest,
+    encryption_instance: &State<'_, EncryptionClientInstance>,
+    llm: &dyn LlmBackend,
+    histories: &ChatHistories,
+    app_handle: &AppHandle,
+    state: &State<'_, GenerateState>,
+    on_content: &mut OnContent<'_>,
+) -> Result<GenerateResult, String> {
+    let cancel = state.begin_generation(&request.chat_id).await;
+    let turn = async {
+        let chat = histories
+            .chat(&request.chat_id, || chats::saved_history(app_handle, &request.chat_id))
+            .await;
+        let mut saved = chat.lock().await;
+        let _permit = state.generation_permit().await;
+
+        let mut history = saved.clone();
+        let result = generate_reply(
+            request,
+            encryption_instance,
+            llm,
+            &mut history,
+            app_handle,
+            state,
+            on_content
+        ).await?;
+        *saved = history;
+        chats::record_exchange(app_handle, &saved, request, &result);
+        Ok(result)
+    };
+
+    let timeout = Duration::from_secs(*GENERATE_TIMEOUT_SECS);
+    let result = tokio::select! {
+        _ = cancel.notified() => {
+            println!("[generate] Generation for chat {} cancelled", request.chat_id);
+            Err("Generation cancelled".to_string())
+        }
+        result = tokio::time::timeout(timeout, turn) => result.unwrap_or_else(|_| {
+            println!("[generate] Generation for chat {} timed out after {}s", request.chat_id, timeout.as_secs());
+            Err(format!("Generation timed out after {}s", timeout.as_secs()))
+        }),
+    };
+    state.end_generation(&request.chat_id, &cancel).await;
+    result
+}
+
+async fn generate_reply(
+    request: &ChatRequest,
+    encryption_instance: &State<'_, EncryptionClientInstance>,
+    llm: &dyn LlmBackend,
+    history: &mut Vec<ChatMessage>,
+    app_handle: &AppHandle,
+    state: &State<'_, GenerateState>,
+    on_content: &mut OnContent<'_>,
+) -> Result<GenerateResult, String> {
+    ensure_catalog(encryption_instance, app_handle, state).await?;
+    if let Some(result) = confirmation_reply(request, encryption_instance, history, app_handle, state).await {
+        return Ok(result);
+    }
+    if let Some(result) = profile_reply(request, encryption_instance, history, app_handle, state).await {
+        return Ok(result);
     }
+    answer(request, llm, history, Some(app_handle), state, true, on_content).await
+}
 
-    let filtered_json = state.get_filtered_json().await;
-    let mut ollama = g_ollama.0.lock().await;
-    let mut seen_chats = seen_chats.0.lock().await;
-
-    if !seen_chats.contains_key(&request.chat_id) {
-        seen_chats.insert(request.chat_id.clone(), true);
-
-        let sys_prompt = format!(
-            r#"
-You're an assistant that only replies in JSON format with keys "message" and "command".
-It is very important that you stick to the following JSON format.
-
-Your main job is to act as a computer accessibility coach that will reply to queries with a JSON
-that has the following keys:
-- "message": Something you want to say to the user
-- "command": A gsettings accessibility command to run
-
-Below is a reference JSON that shows possible accessibility commands 
-for the current environment ({}): 
-
-{}
-
-The prompt will always begin with a snippet of the reference JSON that is the most
-likely command the user is referring to. You will need to add a value to the end 
-of the command found in the "command" field, and use "current" to help you figure 
-out how to decide this new value. Remember, always reply with just the final JSON object, like:
-
-{{
-  "message": "...",
-  "command": "..."
-}}
-"#,
-            platform_info, filtered_json
-        );
-
-        if let Err(e) = ollama
-            .send_chat_messages_with_history(
-                ChatMessageRequest::new(
-                    request.model.clone(),
-                    vec![ChatMessage::system(sys_prompt)]
-                ),
-                request.chat_id.clone()
-            )
-            .await
-        {
-            return Err(format!("Failed to send initial chat message: {}", e));
+// "Switch to my work profile" is answered without the model. Like setting changes,
+// the frontend asks before activating it.
+async fn profile_reply(
+    request: &ChatRequest,
+    encryption_instance: &State<'_, EncryptionClientInstance>,
+    history: &mut Vec<ChatMessage>,
+    app_handle: &AppHandle,
+    state: &State<'_, GenerateState>,
+) -> Option<GenerateResult> {
+    let language = request.language();
+    if !intent::mentions_profile(&request.prompt, language) {
+        return None;
+    }
+    let username = state.get_username(app_handle).await;
+    let names: Vec<String> = match profiles::load_profiles(&username, encryption_instance, state).await {
+        Ok(profiles) => profiles.into_keys().collect(),
+        Err(e) => {
+            println!("[generate] {}", e);
+            return None;
+        }
+    };
+    let name = intent::parse_profile(&request.prompt, &names, language)?;
+    println!("[generate] Read '{}' as a switch to profile '{}'", request.prompt, name);
+
+    let message = language.can_switch_profile(&name);
+    record_rule_exchange(history, &request.prompt, &message, Vec::new());
+
+    Some(GenerateResult {
+        profile: Some(name),
+        ..GenerateResult::new(rule_response(message))
+    })
+}
+
+// "Keep it" or "undo that" while changes are waiting for confirmation settles all
+// of them, the same as the buttons in the confirmation prompt.
+async fn confirmation_reply(
+    request: &ChatRequest,
+    encryption_instance: &State<'_, EncryptionClientInstance>,
+    history: &mut Vec<ChatMessage>,
+    app_handle: &AppHandle,
+    state: &State<'_, GenerateState>,
+) -> Option<GenerateResult> {
+    let pending = state.get_pending_changes().await;
+    if pending.is_empty() {
+        return None;
+    }
+    let language = request.language();
+    let answer = intent::parse_confirmation(&request.prompt, language)?;
+    println!("[generate] Read '{}' as {:?} for {} pending change(s)", request.prompt, answer, pending.len());
+
+    let mut settled = 0;
+    for change in pending {
+        // It may have timed out and been reverted in the meantime.
+        let Some(change) = state.take_pending_change(&change.id).await else {
+            continue;
+        };
+        let result = match answer {
+            intent::Confirmation::Keep => {
+                confirmation::keep_pending(app_handle, encryption_instance.clone(), state.clone(), change).await
+            }
+            intent::Confirmation::Revert => confirmation::revert_pending(app_handle, &change).await,
+        };
+        match result {
+            Ok(()) => settled += 1,
+            Err(e) => println!("[generate] Failed to settle pending change: {}", e),
         }
     }
 
-    let best_match = crate::preferences::find_best_match(&request.prompt, &filtered_json);
-    println!("Best match for prompt '{}': {:?}", request.prompt, best_match);
+    let message = match answer {
+        intent::Confirmation::Keep => language.kept_changes(settled),
+        intent::Confirmation::Revert => language.reverted_changes(settled),
+    };
+    record_rule_exchange(history, &request.prompt, &message, Vec::new());
+    Some(GenerateResult::new(rule_response(message)))
+}
 
-    let best_match_json = match best_match {
-        Some(ref key) => {
-            let parsed_json: Value = serde_json::from_str(&filtered_json).unwrap_or(Value::Null);
+// Runs a request through the same path as a chat message, but without the app: no
+// model status events and no live setting reads. The catalog has to be in `state`.
+// With `rule_fallback` off, a failed model reply is returned as the error instead of
+// being answered by the rule-based parser.
+pub(crate) async fn headless_reply(
+    request: &ChatRequest,
+    llm: &dyn LlmBackend,
+    history: &mut Vec<ChatMessage>,
+    state: &GenerateState,
+    rule_fallback: bool,
+) -> Result<GenerateResult, String> {
+    answer(request, llm, history, None, state, rule_fallback, &mut |_| {}).await
+}
 
-            if let Value::Object(mut root) = parsed_json {
-                if let Some(matching_value) = root.remove(key) {
-                    let mut new_obj = serde_json::Map::new();
-                    new_obj.insert(key.clone(), matching_value);
+// Every proposed action leaves here with a preview the frontend can show in place of
+// the raw command.
+async fn answer(
+    request: &ChatRequest,
+    llm: &dyn LlmBackend,
+    history: &mut Vec<ChatMessage>,
+    app_handle: Option<&AppHandle>,
+    state: &GenerateState,
+    rule_fallback: bool,
+    on_content: &mut OnContent<'_>,
+) -> Result<GenerateResult, String> {
+    let mut result = respond(request, llm, history, app_handle, state, rule_fallback, on_content).await?;
+    if !result.actions.is_empty() {
+        let (config, _) = catalog(state).await;
+        preview::attach(&mut result.actions, &config, request.language());
+        preview::issue(state, &result.actions).await;
+    }
+    Ok(result)
+}
 
-                    let snippet = serde_json::to_string_pretty(&Value::Object(new_obj))
-                        .unwrap_or_else(|_| filtered_json.clone());
+async fn respond(
+    request: &ChatRequest,
+    llm: &dyn LlmBackend,
+    history: &mut Vec<ChatMessage>,
+    app_handle: Option<&AppHandle>,
+    state: &GenerateState,
+    rule_fallback: bool,
+    on_content: &mut OnContent<'_>,
+) -> Result<GenerateResult, String> {
+    let rules_only = request.model == intent::RULES_MODEL || *intent::INTENT_RULES == RulesMode::Only;
+    let rules_first = rules_only || *intent::INTENT_RULES == RulesMode::FastPath;
+    if rules_first {
+        if let Some(result) = rule_reply(request, history, state).await {
+            return Ok(result);
+        }
+        if rules_only {
+            return Ok(GenerateResult::new(rule_response(request.language().rules_only_hint().to_string())));
+        }
+    }
 
-                    state.set_best_match_json(&snippet).await;
-                    snippet
-                } else {
-                    filtered_json.clone()
-                }
-            } else {
-                filtered_json.clone()
+    // The model is unloaded once its keep-alive runs out; the chat shows it loading
+    // until the first reply comes back.
+    let was_ready = state.get_model_status(&request.model).await == ModelStatus::Ready;
+    let loading = app_handle.filter(|_| !was_ready);
+    if let Some(app_handle) = loading {
+        model_manager::set_status(app_handle, &request.model, ModelStatus::Loading, None).await;
+    }
+    let result = model_reply(request, llm, history, app_handle, state, on_content).await;
+    match loading {
+        Some(app_handle) => {
+            let status = if result.is_ok() { ModelStatus::Ready } else { ModelStatus::Failed };
+            model_manager::set_status(app_handle, &request.model, status, result.as_ref().err().cloned()).await;
+        }
+        None if result.is_ok() => model_manager::mark_used(app_handle, state, &request.model).await,
+        None => {}
+    }
+    match result {
+        Err(e) if rule_fallback && !rules_first => match rule_reply(request, history, state).await {
+            Some(result) => {
+                println!("[generate] {}; answered with the rule-based parser instead", e);
+                Ok(result)
+            }
+            None => Err(e),
+        },
+        result => result,
+    }
+}
+
+async fn model_reply(
+    request: &ChatRequest,
+    llm: &dyn LlmBackend,
+    history: &mut Vec<ChatMessage>,
+    app_handle: Option<&AppHandle>,
+    state: &GenerateState,
+    on_content: &mut OnContent<'_>,
+) -> Result<GenerateResult, String> {
+    let prepared = prepare_chat(
+        request,
+        llm,
+        history,
+        app_handle,
+        state
+    ).await?;
+    let user_prompt = prepared.user_prompt.clone();
+
+    let mut messages = history.clone();
+    messages.push(ChatMessage::user(user_prompt.clone()));
+    let chat = LlmRequest::new(&request.model, messages, prepared.window);
+
+    let (config, env) = catalog(state).await;
+    let tools = tools::tool_definitions(&config, &env, request.language());
+    if !tools.is_empty() {
+        match llm.chat_stream(&chat.clone().tools(tools), on_content).await {
+            Ok(reply) => {
+                println!("Received tool reply: {:?} with {} tool call(s)", reply.message.content, reply.tool_calls.len());
+                return finish_tool_reply(history, request, user_prompt, reply, app_handle, state)
+                    .await
+                    .map(|result| prepared.annotate(result));
+            }
+            Err(LlmError::ToolsUnsupported) => {
+                println!("[generate] {} does not support tools, falling back to JSON replies", request.model);
             }
+            Err(e) => return Err(format!("Failed to generate text: {}", e)),
         }
+    }
+
+    let res = llm
+        .chat_stream(&chat.format(model_response_format()), on_content)
+        .await
+        .map_err(|e| format!("Failed to generate text: {}", e))?;
+    println!("Received initial response: {:?}", res);
+
+    history.push(ChatMessage::user(user_prompt));
+    history.push(res.message.clone());
+
+    finish_response(llm, history, request, res, prepared.window, app_handle, state)
+        .await
+        .map(|result| prepared.annotate(result))
+}
+
+fn emit_chunk(app_handle: &AppHandle, chat_id: &str, content: &str, last_message: &mut String) {
+    let partial = if content.trim_start().starts_with('{') {
+        partial_json_string_field(content, "message")
+    } else {
+        Some(content.to_string())
+    };
+
+    let Some(partial) = partial else {
+        return;
+    };
+    if partial == *last_message {
+        return;
+    }
+    *last_message = partial;
+
+    if let Err(e) = app_handle.emit("generate-chunk", GenerateChunk {
+        chat_id: chat_id.to_string(),
+        message: last_message.clone(),
+    }) {
+        println!("[generate_stream] Failed to emit chunk: {}", e);
+    }
+}
+
+async fn catalog(state: &GenerateState) -> (AppConfig, String) {
+    let config = serde_json::from_str(&state.get_full_json().await).unwrap_or_default();
+    (config, state.get_platform_info().await)
+}
+
+struct PreparedPrompt {
+    user_prompt: String,
+    window: usize,
+    matches: Vec<SettingMatch>,
+    low_confidence: bool,
+}
+
+impl PreparedPrompt {
+    fn annotate(&self, mut result: GenerateResult) -> GenerateResult {
+        result.matches = self.matches.clone();
+        result.low_confidence = self.low_confidence;
+        result
+    }
+}
+
+// Answers from the catalog alone.
+async fn rule_reply(
+    request: &ChatRequest,
+    history: &mut Vec<ChatMessage>,
+    state: &GenerateState,
+) -> Option<GenerateResult> {
+    let (config, env) = catalog(state).await;
+    let filtered_json = state.get_filtered_json().await;
+    let language = request.language();
+    let parsed = intent::parse(&request.prompt, &config, &filtered_json, &env, language, request.setting.as_deref())?;
+
+    let settings: Vec<&str> = parsed.actions.iter().map(|action| action.setting.as_str()).collect();
+    let message = language.can_change(&settings.join(", "));
+    let commands: Vec<String> = parsed.actions.iter().map(|action| action.command.clone()).collect();
+    println!("[generate] Rule-based parser read '{}' as {:?}", request.prompt, commands);
+    record_rule_exchange(history, &request.prompt, &message, commands);
+
+    Some(GenerateResult {
+        command: parsed.actions.first().map(|action| action.command.clone()),
+        actions: parsed.actions,
+        matches: parsed.matches,
+        ..GenerateResult::new(rule_response(message))
+    })
+}
+
+// Replies made without the model are stored the way the model would have replied,
+// so a later model turn sees a consistent chat.
+fn record_rule_exchange(history: &mut Vec<ChatMessage>, prompt: &str, message: &str, commands: Vec<String>) {
+    let response = ModelResponse {
+        message: message.to_string(),
+        kind: ReplyKind::Action,
+        commands,
+        choices: Vec::new(),
+        queries: Vec::new(),
+        command: None,
+    };
+    history.push(ChatMessage::user(prompt.to_string()));
+    history.push(ChatMessage::assistant(serde_json::to_string(&response).unwrap_or_default()));
+}
+
+fn rule_response(message: String) -> LlmReply {
+    LlmReply {
+        model: intent::RULES_MODEL.to_string(),
+        created_at: String::new(),
+        message: ChatMessage::assistant(message),
+        done: true,
+        tool_calls: Vec::new(),
+    }
+}
+
+async fn ensure_catalog(
+    encryption_instance: &State<'_, EncryptionClientInstance>,
+    app_handle: &AppHandle,
+    state: &State<'_, GenerateState>
+) -> Result<(), String> {
+    if !state.get_full_json().await.is_empty() {
+        return Ok(());
+    }
+    println!("[generate] Full JSON empty; fetching preferences from server...");
+
+    let username = state.get_username(app_handle).await;
+    let platform_info = state.get_platform_info().await;
+    crate::preferences::fetch_preferences_impl(
+        &username,
+        encryption_instance,
+        &platform_info,
+        state
+    )
+        .await
+        .map(|_| ())
+        .map_err(|err| format!("Failed to automatically fetch preferences: {}", err))
+}
+
+async fn prepare_chat(
+    request: &ChatRequest,
+    llm: &dyn LlmBackend,
+    history: &mut Vec<ChatMessage>,
+    app_handle: Option<&AppHandle>,
+    state: &GenerateState
+) -> Result<PreparedPrompt, String> {
+    let platform_info = state.get_platform_info().await;
+    let filtered_json = state.get_filtered_json().await;
+    let window = context::context_window(llm, state, &request.model).await;
+
+    let template = prompts::template_for(&request.model);
+    let language = request.language();
+    if !history.iter().any(|message| message.role == MessageRole::System) {
+        let catalog = context::fit_catalog(&without_translations(&filtered_json), window);
+        let sys_prompt = template.system_prompt(&PromptVariables {
+            environment: &platform_info,
+            catalog: &catalog,
+            best_match: "",
+            locale: language.code(),
+            language_instructions: language.prompt_instructions(),
+            prompt: "",
+        });
+
+        history.insert(0, ChatMessage::system(sys_prompt));
+    }
+
+    let (matches, low_confidence) = match request.setting.clone() {
+        Some(setting) => (vec![SettingMatch { setting, score: 1.0 }], false),
         None => {
-            let old_snippet = state.get_best_match_json().await;
-            if !old_snippet.is_empty() {
-                old_snippet
-            } else {
-                filtered_json.clone()
+            let ranking = retrieval::rank(app_handle, llm, state, &request.prompt, &filtered_json, request.language()).await;
+            (ranking.matches, !ranking.confident)
+        }
+    };
+    println!("Matches for prompt '{}': {:?}", request.prompt, matches);
+
+    // A confident match narrows the reference down to that one setting. Below the
+    // threshold the model sees every candidate and is nudged to ask rather than guess.
+    let best_match_json = if !low_confidence {
+        let snippet = settings_snippet(&filtered_json, &matches[..1]).unwrap_or_else(|| without_translations(&filtered_json));
+        state.set_best_match_json(&request.chat_id, &snippet).await;
+        snippet
+    } else if let Some(candidates) = settings_snippet(&filtered_json, &matches) {
+        format!("{}\n\n{}", candidates, NO_MATCH_HINT)
+    } else {
+        let old_snippet = state.get_best_match_json(&request.chat_id).await;
+        let context = if !old_snippet.is_empty() {
+            old_snippet
+        } else {
+            without_translations(&filtered_json)
+        };
+        format!("{}\n\n{}", context, NO_MATCH_HINT)
+    };
+
+    let user_prompt = template.user_prompt(&PromptVariables {
+        environment: &platform_info,
+        catalog: "",
+        best_match: &best_match_json,
+        locale: language.code(),
+        language_instructions: "",
+        prompt: &context::marked_prompt(&request.prompt),
+    });
+    context::fit_history(llm, &request.model, &request.chat_id, history, window, &user_prompt).await;
+
+    Ok(PreparedPrompt {
+        user_prompt,
+        window,
+        matches,
+        low_confidence,
+    })
+}
+
+fn settings_snippet(filtered_json: &str, matches: &[SettingMatch]) -> Option<String> {
+    let Ok(Value::Object(mut root)) = serde_json::from_str::<Value>(filtered_json) else {
+        return None;
+    };
+
+    let mut snippet = serde_json::Map::new();
+    for m in matches {
+        if let Some(mut value) = root.remove(&m.setting) {
+            if let Value::Object(setting) = &mut value {
+                setting.remove("translations");
             }
+            snippet.insert(m.setting.clone(), value);
         }
+    }
+    if snippet.is_empty() {
+        return None;
+    }
+    serde_json::to_string_pretty(&Value::Object(snippet)).ok()
+}
+
+// Translations are only used for matching; the model is told which language to reply
+// in instead of reading every translation of every setting.
+fn without_translations(filtered_json: &str) -> String {
+    let Ok(Value::Object(mut root)) = serde_json::from_str::<Value>(filtered_json) else {
+        return filtered_json.to_string();
     };
+    for setting in root.values_mut() {
+        if let Value::Object(setting) = setting {
+            setting.remove("translations");
+        }
+    }
+    serde_json::to_string_pretty(&Value::Object(root)).unwrap_or_else(|_| filtered_json.to_string())
+}
 
-    let user_prompt = format!("{}\n\n {}", best_match_json, request.prompt);
+async fn finish_response(
+    llm: &dyn LlmBackend,
+    history: &mut Vec<ChatMessage>,
+    request: &ChatRequest,
+    mut res: LlmReply,
+    window: usize,
+    app_handle: Option<&AppHandle>,
+    state: &GenerateState,
+) -> Result<GenerateResult, String> {
+    let mut parsed = parse_model_response(&res.message.content);
+    for attempt in 1..=MAX_REPAIR_ATTEMPTS {
+        let Err(ref e) = parsed else {
+            break;
+        };
+        println!("[generate] Reply did not parse ({}), asking again ({}/{})", e, attempt, MAX_REPAIR_ATTEMPTS);
 
-    match ollama
-        .send_chat_messages_with_history(
-            ChatMessageRequest::new(
-                request.model, 
-                vec![ChatMessage::user(user_prompt)]
-            ),
-            request.chat_id,
-        )
-        .await
-    {
-        Ok(mut res) => {
-            println!("Received initial response: {:?}", res);
-            let response_str = res
-                .message
-                .as_ref()
-                .map(|m| m.content.clone())
-                .unwrap_or_default();
-
-            match serde_json::from_str::<ModelResponse>(&response_str) {
-                Ok(parsed_response) => {
-                    res.message = Some(ChatMessage::new(
-                        MessageRole::Assistant,
-                        parsed_response.message.clone(),
-                    ));
-
-                    Ok(GenerateResult {
-                        ollama_response: res,
-                        command: Some(parsed_response.command),
-                    })
-                }
-                Err(e) => Err(format!("Failed to parse model response: {}", e)),
+        let repair = ChatMessage::user(REPAIR_PROMPT.to_string());
+        let mut messages = history.clone();
+        messages.push(repair.clone());
+        res = llm
+            .chat(&LlmRequest::new(&request.model, messages, window).format(model_response_format()))
+            .await
+            .map_err(|e| format!("Failed to generate text: {}", e))?;
+        history.push(repair);
+        history.push(res.message.clone());
+        parsed = parse_model_response(&res.message.content);
+    }
+
+    let parsed_response = parsed.map_err(|e| format!("Failed to parse model response: {}", e))?;
+    res.message = ChatMessage::assistant(parsed_response.message.clone());
+
+    let (config, env) = catalog(state).await;
+    match parsed_response.kind {
+        ReplyKind::Clarify => {
+            return Ok(clarify_result(res, valid_choices(&config, &env, parsed_response.choices)));
+        }
+        ReplyKind::Query => {
+            let keys = valid_choices(&config, &env, parsed_response.queries);
+            return Ok(query_result(res, &keys, &config, &env, request.language(), app_handle).await);
+        }
+        ReplyKind::Action => {}
+    }
+    let actions = actions_from_commands(&config, &env, &parsed_response.all_commands());
+
+    Ok(GenerateResult {
+        command: actions.first().map(|action| action.command.clone()),
+        actions,
+        ..GenerateResult::new(res)
+    })
+}
+
+// Each tool call is validated against the catalog; invalid ones are dropped.
+async fn finish_tool_reply(
+    history: &mut Vec<ChatMessage>,
+    request: &ChatRequest,
+    user_prompt: String,
+    mut reply: LlmReply,
+    app_handle: Option<&AppHandle>,
+    state: &GenerateState,
+) -> Result<GenerateResult, String> {
+    let content = reply.message.content.clone();
+    let tool_calls = std::mem::take(&mut reply.tool_calls);
+    let (config, env) = catalog(state).await;
+
+    // The calls stay on the assistant message, each followed by its result, so the
+    // model sees what came of them on the next turn.
+    history.push(ChatMessage::user(user_prompt));
+    let mut called = reply.message.clone();
+    called.tool_calls = tool_calls.clone();
+    history.push(called);
+    history.extend(tool_calls.iter().map(|call| ChatMessage::tool(tool_result(&config, &env, call))));
+
+    let clarification = tool_calls
+        .iter()
+        .find_map(|call| tools::clarification_from_tool_call(&config, &env, call));
+    if let Some((question, choices)) = clarification {
+        reply.message = ChatMessage::assistant(question);
+        return Ok(clarify_result(reply, choices));
+    }
+
+    let mut actions: Vec<SettingAction> = tool_calls
+        .iter()
+        .filter(|call| ![tools::CLARIFY_TOOL, tools::QUERY_TOOL].contains(&call.function.name.as_str()))
+        .filter_map(|call| match tools::action_from_tool_call(&config, &env, call) {
+            Ok(action) => Some(action),
+            Err(e) => {
+                println!("[generate] Ignoring tool call: {}", e);
+                None
+            }
+        })
+        .collect();
+
+    let queried = tool_calls
+        .iter()
+        .find_map(|call| tools::query_from_tool_call(&config, &env, call));
+    if let Some(keys) = queried.filter(|_| actions.is_empty()) {
+        return Ok(query_result(reply, &keys, &config, &env, request.language(), app_handle).await);
+    }
+
+    // Some models answer in the JSON format from the system prompt even with tools on.
+    let mut message = content.trim().to_string();
+    if let Ok(parsed) = parse_model_response(&content) {
+        if actions.is_empty() && parsed.kind == ReplyKind::Clarify {
+            reply.message = ChatMessage::assistant(parsed.message);
+            return Ok(clarify_result(reply, valid_choices(&config, &env, parsed.choices)));
+        }
+        if actions.is_empty() && parsed.kind == ReplyKind::Query {
+            let keys = valid_choices(&config, &env, parsed.queries);
+            return Ok(query_result(reply, &keys, &config, &env, request.language(), app_handle).await);
+        }
+        if actions.is_empty() {
+            actions = actions_from_commands(&config, &env, &parsed.all_commands());
+        }
+        message = parsed.message;
+    }
+    if message.is_empty() && !actions.is_empty() {
+        let settings: Vec<&str> = actions.iter().map(|action| action.setting.as_str()).collect();
+        message = request.language().can_change(&settings.join(", "));
+    }
+
+    reply.message = ChatMessage::assistant(message);
+
+    Ok(GenerateResult {
+        command: actions.first().map(|action| action.command.clone()),
+        actions,
+        ..GenerateResult::new(reply)
+    })
+}
+
+fn tool_result(config: &AppConfig, env: &str, call: &crate::llm::ToolCall) -> String {
+    match call.function.name.as_str() {
+        tools::CLARIFY_TOOL => "The question was shown to the user.".to_string(),
+        tools::QUERY_TOOL => "The current values were shown to the user.".to_string(),
+        _ => match tools::action_from_tool_call(config, env, call) {
+            Ok(action) => format!("Proposed setting {} to {}; the user decides whether to apply it.", action.setting, action.value),
+            Err(e) => format!("Not applied: {}", e),
+        },
+    }
+}
+
+// Questions about a setting are answered from the live system value rather than the
+// stored preference, and nothing is executed.
+async fn query_result(
+    mut res: LlmReply,
+    keys: &[String],
+    config: &AppConfig,
+    env: &str,
+    language: Language,
+    app_handle: Option<&AppHandle>,
+) -> GenerateResult {
+    let readings = match app_handle {
+        Some(app_handle) => settings::read_settings(app_handle, config, env, keys).await,
+        None => Vec::new(),
+    };
+    if !readings.is_empty() {
+        res.message = ChatMessage::assistant(settings::describe_readings(&readings, config, language));
+    }
+    GenerateResult {
+        kind: ReplyKind::Query,
+        readings,
+        ..GenerateResult::new(res)
+    }
+}
+
+// A clarifying question only makes sense with something to pick from; with no usable
+// choices it is shown as a plain reply.
+fn clarify_result(res: LlmReply, choices: Vec<String>) -> GenerateResult {
+    let kind = if choices.is_empty() { ReplyKind::Action } else { ReplyKind::Clarify };
+    GenerateResult {
+        kind,
+        choices,
+        ..GenerateResult::new(res)
+    }
+}
+
+fn actions_from_commands(config: &AppConfig, env: &str, commands: &[String]) -> Vec<SettingAction> {
+    commands
+        .iter()
+        .filter_map(|command| match tools::action_from_command(config, env, command) {
+            Ok(action) => Some(action),
+            Err(e) => {
+                println!("[generate] Ignoring command from reply: {}", e);
+                None
             }
+        })
+        .collect()
+}
+
+fn model_response_format() -> Value {
+    let schema = SchemaSettings::draft07()
+        .with(|settings| {
+            settings.inline_subschemas = true;
+            settings.meta_schema = None;
+        })
+        .into_generator()
+        .into_root_schema_for::<ModelResponse>();
+
+    serde_json::to_value(schema).unwrap_or_else(|_| Value::String("json".to_string()))
+}
+
+// Models that ignore the schema usually still produce the object, just wrapped in a
+// code fence or surrounded by prose, so try to cut it out before giving up.
+fn parse_model_response(raw: &str) -> Result<ModelResponse, String> {
+    let direct_err = match serde_json::from_str::<ModelResponse>(raw.trim()) {
+        Ok(parsed) => return Ok(parsed),
+        Err(e) => e.to_string(),
+    };
+
+    extract_json_object(raw)
+        .and_then(|candidate| serde_json::from_str::<ModelResponse>(candidate).ok())
+        .ok_or(direct_err)
+}
+
+// Pulls the (possibly unterminated) value of a top-level string field out of JSON
+// that is still being streamed, so the chat can show the message as it's typed.
+fn partial_json_string_field(raw: &str, field: &str) -> Option<String> {
+    let key = format!("\"{}\"", field);
+    let after_key = &raw[raw.find(&key)? + key.len()..];
+    let after_colon = after_key.trim_start().strip_prefix(':')?;
+    let mut chars = after_colon.trim_start().strip_prefix('"')?.chars();
+
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('r') => value.push('\r'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        Some(decoded) if hex.len() == 4 => value.push(decoded),
+                        _ => break,
+                    }
+                }
+                Some(other) => value.push(other),
+                None => break,
+            },
+            _ => value.push(c),
         }
-        Err(e) => Err(format!("Failed to generate text: {}", e)),
     }
+    Some(value)
+}
+
+fn extract_json_object(raw: &str) -> Option<&str> {
+    let body = match raw.find("```") {
+        Some(fence) => {
+            let after = &raw[fence + 3..];
+            let after = after.strip_prefix("json").unwrap_or(after);
+            after.split("```").next()?
+        }
+        None => raw,
+    };
+
+    let start = body.find('{')?;
+    let end = body.rfind('}')?;
+    (start < end).then(|| &body[start..=end])
 }
 
+// The value is passed as one argument, so string values may contain spaces
+// ("Cantarell 13").
 pub async fn execute_command_impl(
-    command: String,
+    base_command: String,
+    value: String,
     update: bool,
+    source: ChangeSource,
     app_handle: AppHandle,
     encryption_instance: State<'_, EncryptionClientInstance>,
     state: State<'_, GenerateState>,
 ) -> Result<(), String> {
-    let command_parts: Vec<&str> = command.split_whitespace().collect();
-    if command_parts.len() < 2 {
-        return Err(format!("Invalid command format: must have base command + 1 argument"));
+    let base_cmd_str = base_command.trim().to_string();
+    let last_value = value.trim();
+    if base_cmd_str.is_empty() || last_value.is_empty() {
+        return Err("Invalid command format: must have base command + 1 argument".to_string());
     }
-
-    let (base_parts, value_part) = command_parts.split_at(command_parts.len() - 1);
-    let last_value = value_part.first().unwrap();
-    let base_cmd_str = base_parts.join(" ");
+    let command = format!("{} {}", base_cmd_str, last_value);
 
     let platform_info = state.get_platform_info().await;
     let valid_commands = gather_valid_commands_for_env(&state, &platform_info).await?;
     if !valid_commands.contains(&base_cmd_str) {
         return Err(format!("Unrecognized/unauthorized command base: '{}'. Will not execute command.", base_cmd_str));
     }
-
+    let previous = find_setting_for_command(&state.get_full_json().await, &platform_info, &base_cmd_str);
     println!("Attempting to run shell command: {}", command);
-    let shell = app_handle.shell();
     let username = match super::get_username(app_handle.clone()).await {
         Ok(username) => username,
         Err(e) => {
@@ -185,34 +864,158 @@ pub async fn execute_command_impl(
         }
     };
 
-    match shell.command(&base_parts[0]).args(&base_parts[1..]).arg(last_value).output().await {
+    let stdout_str = run_setting_command(&app_handle, &base_cmd_str, last_value).await?;
+    println!("Command result: {:?}", stdout_str);
+    if let Some((key, _)) = &previous {
+        confirmation::supersede_pending(&app_handle, &state, key).await;
+    }
+
+    if let Some((key, setting)) = previous {
+        let new_value = parse_new_value(last_value, &setting.current);
+        if confirmation::needs_confirmation(&setting, &new_value, source) {
+            let pending = confirmation::hold_for_confirmation(
+                &app_handle,
+                &state,
+                PendingChange {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    setting: key,
+                    command: base_cmd_str,
+                    old_value: setting.current.clone(),
+                    new_value,
+                    source,
+                    timeout_secs: *confirmation::CONFIRM_TIMEOUT_SECS,
+                    update,
+                },
+            ).await;
+            println!("[execute_command] Applied provisionally, waiting {}s for confirmation of {}", pending.timeout_secs, pending.id);
+            return Ok(());
+        }
+
+        let old_value = (source != ChangeSource::Startup).then(|| setting.current.clone());
+        state.record_change(&key, &base_cmd_str, old_value, new_value, source).await;
+    }
+
+    if update {
+        if let Err(err) = update_json_current_value(
+            &username,
+            &base_cmd_str,
+            last_value,
+            encryption_instance,
+            state,
+        ).await {
+            println!("Warning: error updating JSON current value: {}", err);
+        }
+    }
+    println!("[execute_command] Command executed: {}", command);
+
+    Ok(())
+}
+
+// Runs the actions in order. If one fails, the ones already applied are put back
+// to their previous values (newest first) and dropped from the change history, so a
+// reply either takes effect as a whole or not at all.
+pub async fn execute_actions_impl(
+    actions: Vec<SettingAction>,
+    update: bool,
+    source: ChangeSource,
+    app_handle: AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<(), String> {
+    let (config, env) = catalog(&state).await;
+    let mut applied: Vec<(SettingAction, DefaultValue)> = Vec::new();
+
+    for action in actions {
+        let Some(previous) = config.get(&action.setting).map(|setting| setting.current.clone()) else {
+            rollback_actions(applied, update, &app_handle, &encryption_instance, &state).await;
+            return Err(format!("Unknown setting '{}'", action.setting));
+        };
+
+        let Some((base_command, value)) = preferences::split_command(&config, &env, &action.command) else {
+            rollback_actions(applied, update, &app_handle, &encryption_instance, &state).await;
+            return Err(format!("Command for '{}' is not in the catalog", action.setting));
+        };
+        if let Err(e) = execute_command_impl(
+            base_command,
+            value,
+            update,
+            source,
+            app_handle.clone(),
+            encryption_instance.clone(),
+            state.clone(),
+        ).await {
+            println!("[execute_actions] '{}' failed, rolling back {} earlier action(s)", action.setting, applied.len());
+            rollback_actions(applied, update, &app_handle, &encryption_instance, &state).await;
+            return Err(format!("Failed to change '{}': {}", action.setting, e));
+        }
+        applied.push((action, previous));
+    }
+    Ok(())
+}
+
+async fn rollback_actions(
+    applied: Vec<(SettingAction, DefaultValue)>,
+    update: bool,
+    app_handle: &AppHandle,
+    encryption_instance: &State<'_, EncryptionClientInstance>,
+    state: &State<'_, GenerateState>,
+) {
+    let (config, env) = catalog(state).await;
+
+    for (action, previous) in applied.into_iter().rev() {
+        if let Some(pending) = state.take_pending_change_for_setting(&action.setting).await {
+            if let Err(e) = confirmation::revert_pending(app_handle, &pending).await {
+                println!("[execute_actions] Failed to roll back '{}': {}", action.setting, e);
+            }
+            continue;
+        }
+
+        let Some(setting) = config.get(&action.setting) else {
+            continue;
+        };
+        match execute_command_impl(
+            preferences::command_for_env(setting, &env),
+            previous.to_string(),
+            update,
+            ChangeSource::Rollback,
+            app_handle.clone(),
+            encryption_instance.clone(),
+            state.clone(),
+        ).await {
+            Ok(()) => {
+                state.discard_last_change(&action.setting).await;
+            }
+            Err(e) => println!("[execute_actions] Failed to roll back '{}': {}", action.setting, e),
+        }
+    }
+}
+
+pub async fn run_setting_command(
+    app_handle: &AppHandle,
+    base_command: &str,
+    value: &str,
+) -> Result<String, String> {
+    let base_parts: Vec<&str> = base_command.split_whitespace().collect();
+    let Some((program, args)) = base_parts.split_first() else {
+        return Err("Invalid command format: empty base command".to_string());
+    };
+
+    let shell = app_handle.shell();
+    match shell.command(program).args(args).arg(value).output().await {
         Ok(output) => {
             if output.status.success() {
-                let stdout_str = String::from_utf8(output.stdout).unwrap_or_default();
-                println!("Command result: {:?}", stdout_str);
-
-                if update {
-                    if let Err(err) = update_json_current_value(
-                        &username,
-                        &base_cmd_str,
-                        last_value,
-                        encryption_instance,
-                        state,
-                    ).await {
-                        println!("Warning: error updating JSON current value: {}", err);
-                    }
-                }
+                Ok(String::from_utf8(output.stdout).unwrap_or_default())
             } else {
-                println!("Exit with code: {}", output.status.code().unwrap_or_default());
+                let code = output.status.code().unwrap_or_default();
+                println!("Exit with code: {}", code);
+                Err(format!("Command exited with code {}: {} {}", code, base_command, value))
             }
         }
         Err(e) => {
-            println!("Failed to execute command: {} with error {}", command, e);
+            println!("Failed to execute command: {} {} with error {}", base_command, value, e);
+            Err(format!("Failed to execute command: {}", e))
         }
     }
-    println!("[execute_command] Command executed: {}", command);
-
-    Ok(())
 }
 
 pub async fn execute_command_app_impl(
@@ -252,3 +1055,99 @@ pub async fn execute_command_app_impl(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::fake::{FakeBackend, FakeReply};
+    use crate::llm::{ToolCall, ToolCallFunction};
+    use crate::models::PlatformInfo;
+    use crate::preferences::filter_json_by_env;
+
+    const TEXT_SIZE: &str = "gsettings set org.gnome.desktop.interface text-scaling-factor";
+
+    async fn catalog_state() -> GenerateState {
+        let catalog = include_str!("../json_example.json");
+        let state = GenerateState::default();
+        state.set_platform(PlatformInfo { env: "gnome".to_string(), ..Default::default() });
+        state.update_jsons(catalog, &filter_json_by_env(catalog, "gnome").unwrap()).await;
+        state
+    }
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            model: "fake".to_string(),
+            prompt: "the letters are too small for me".to_string(),
+            chat_id: "test".to_string(),
+            setting: None,
+            locale: Some("en-US".to_string()),
+        }
+    }
+
+    async fn reply(llm: &FakeBackend) -> Result<GenerateResult, String> {
+        let state = catalog_state().await;
+        headless_reply(&request(), llm, &mut Vec::new(), &state, false).await
+    }
+
+    #[tokio::test]
+    async fn json_replies_become_previewed_actions() {
+        let llm = FakeBackend::default();
+        llm.push_reply(FakeReply::text(&format!(r#"{{"message": "Making text bigger.", "commands": ["{} 1.5"]}}"#, TEXT_SIZE)));
+
+        let result = reply(&llm).await.unwrap();
+        assert_eq!(result.ollama_response.message.content, "Making text bigger.");
+        assert_eq!(result.actions.len(), 1);
+        assert_eq!(result.actions[0].setting, "zoom");
+        assert_eq!(result.actions[0].value, DefaultValue::Float(1.5));
+        assert!(result.actions[0].preview.is_some());
+        assert_eq!(llm.remaining_replies(), 0);
+    }
+
+    #[tokio::test]
+    async fn tool_calls_become_actions() {
+        let llm = FakeBackend::new(vec!["fake".to_string()], true);
+        llm.push_reply(FakeReply {
+            content: String::new(),
+            tool_calls: vec![ToolCall {
+                function: ToolCallFunction { name: "zoom".to_string(), arguments: serde_json::json!({ "value": 1.25 }) },
+            }],
+        });
+
+        let state = catalog_state().await;
+        let mut history = Vec::new();
+        let result = headless_reply(&request(), &llm, &mut history, &state, false).await.unwrap();
+        assert_eq!(result.actions.len(), 1);
+        assert_eq!(result.actions[0].command, format!("{} 1.25", TEXT_SIZE));
+        assert!(!llm.requests()[0].tools.is_empty());
+
+        let roles: Vec<MessageRole> = history.iter().map(|message| message.role).collect();
+        assert_eq!(roles[roles.len() - 2..], [MessageRole::Assistant, MessageRole::Tool]);
+        assert_eq!(history[history.len() - 2].tool_calls[0].function.name, "zoom");
+        assert!(history[history.len() - 1].content.starts_with("Proposed setting zoom to 1.25"));
+    }
+
+    #[tokio::test]
+    async fn unparsable_replies_are_asked_for_again() {
+        let llm = FakeBackend::default();
+        llm.push_reply(FakeReply::text("Sure, I'll make the text bigger!"));
+        llm.push_reply(FakeReply::text(&format!(r#"{{"message": "Making text bigger.", "commands": ["{} 1.5"]}}"#, TEXT_SIZE)));
+
+        let result = reply(&llm).await.unwrap();
+        assert_eq!(result.actions.len(), 1);
+        let repair = llm.requests().last().unwrap().messages.last().unwrap().content.clone();
+        assert_eq!(repair, REPAIR_PROMPT);
+    }
+
+    #[tokio::test]
+    async fn replies_that_never_parse_are_an_error() {
+        let llm = FakeBackend::default();
+        llm.push_reply(FakeReply::text("Sure, I'll make the text bigger!"));
+        llm.push_reply(FakeReply::text("Done, the text is bigger now."));
+
+        let Err(error) = reply(&llm).await else {
+            panic!("a reply that never parses should fail");
+        };
+        assert!(error.starts_with("Failed to parse model response"), "{}", error);
+        assert_eq!(llm.remaining_replies(), 0);
+    }
+}
@@ -0,0 +1,86 @@
+use tauri::{AppHandle, State};
+use crate::models::{ChangeRecord, ChangeSource, SettingDiff};
+use crate::profiles;
+use crate::state::{EncryptionClientInstance, GenerateState};
+use super::generation::execute_command_impl;
+
+#[tauri::command]
+pub async fn get_change_history(state: State<'_, GenerateState>) -> Result<Vec<ChangeRecord>, String> {
+    Ok(state.get_history().await)
+}
+
+#[tauri::command]
+pub async fn undo_change(
+    app_handle: AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<Option<ChangeRecord>, String> {
+    let Some(record) = state.take_undo().await else {
+        return Ok(None);
+    };
+    let Some(old_value) = record.old_value.clone() else {
+        state.push_applied(record).await;
+        return Ok(None);
+    };
+
+    println!("[history] Undoing change {} to '{}': {} {}", record.id, record.setting, record.command, old_value);
+    if let Err(e) = execute_command_impl(
+        record.command.clone(),
+        old_value.to_string(),
+        true,
+        ChangeSource::Undo,
+        app_handle,
+        encryption_instance,
+        state.clone(),
+    ).await {
+        state.push_applied(record).await;
+        return Err(e);
+    }
+
+    state.push_undone(record.clone()).await;
+    Ok(Some(record))
+}
+
+#[tauri::command]
+pub async fn redo_change(
+    app_handle: AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<Option<ChangeRecord>, String> {
+    let Some(record) = state.take_redo().await else {
+        return Ok(None);
+    };
+
+    println!("[history] Redoing change {} to '{}': {} {}", record.id, record.setting, record.command, record.new_value);
+    if let Err(e) = execute_command_impl(
+        record.command.clone(),
+        record.new_value.to_string(),
+        true,
+        ChangeSource::Redo,
+        app_handle,
+        encryption_instance,
+        state.clone(),
+    ).await {
+        state.push_undone(record).await;
+        return Err(e);
+    }
+
+    state.push_applied(record.clone()).await;
+    Ok(Some(record))
+}
+
+#[tauri::command]
+pub async fn restore_to(
+    timestamp: u64,
+    app_handle: AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<Vec<SettingDiff>, String> {
+    let values = state.history_values_at(timestamp).await;
+    let env = state.get_platform_info().await;
+    let config = profiles::current_config(&app_handle, &encryption_instance, &state).await?;
+    let diffs = profiles::diff_values(&config, &env, &values);
+    println!("[history] Restoring to {}: {} setting(s) differ", timestamp, diffs.len());
+
+    profiles::apply_diffs(diffs, &config, &env, ChangeSource::Restore, &app_handle, &encryption_instance, &state).await
+}
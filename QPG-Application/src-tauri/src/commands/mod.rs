@@ -1,45 +1,35 @@
 pub mod startup;
 pub mod generation;
+pub mod profiles;
+pub mod presets;
+pub mod history;
+pub mod confirmation;
+pub mod settings;
+pub mod chats;
+pub mod models;
 
 pub use startup::init_startup_commands;
 pub use startup::init_startup_apps;
+pub use profiles::{
+    list_profiles, get_active_profile, create_profile, rename_profile,
+    delete_profile, diff_profile, activate_profile
+};
+pub use presets::{list_presets, reload_presets, preview_preset, apply_preset};
+pub use history::{get_change_history, undo_change, redo_change, restore_to};
+pub use confirmation::{list_pending_changes, confirm_change, revert_change};
+pub use settings::read_live_settings;
+pub use chats::{list_chats, open_chat, rename_chat, delete_chat, export_chat};
+pub use models::{
+    list_models, pull_model, cancel_model_pull, delete_model, recommend_model,
+    warm_up_model, get_model_status
+};
 
 use tauri::State;
-use crate::state::{EncryptionClientInstance, OllamaInstance, ChatIDs, GenerateState};
-use crate::preferences;
-use crate::models::{ChatRequest, GenerateResult};
+use crate::state::{EncryptionClientInstance, LlmInstance, ChatHistories, GenerateState};
+use crate::{preferences, preview};
+use crate::models::{AppConfig, ChangeSource, ChatRequest, GenerateResult, PlatformInfo, SettingAction};
 use tauri_plugin_shell::ShellExt;
 
-#[tauri::command]
-pub async fn list_models() -> Result<Vec<String>, String> {
-    use ollama_rs::Ollama;
-    let ollama = Ollama::new_with_history_from_url(
-        url::Url::parse(&preferences::OLLAMA_BASE_URL.to_string()).unwrap(),
-        50,
-    );
-    let default_model_name = "granite3-dense:8b".to_string();
-
-    let local_models = match ollama.list_local_models().await {
-        Ok(res) => res,
-        Err(e) => return Err(format!("Failed to list models: {}", e)),
-    };
-
-    if local_models.is_empty() {
-        println!("No local models found. Pulling {}...", default_model_name);
-        if let Err(e) = ollama.pull_model(default_model_name.into(), false).await {
-            return Err(format!("Failed to pull model: {}", e));
-        }
-    }
-
-    let updated_models = match ollama.list_local_models().await {
-        Ok(res) => res,
-        Err(e) => return Err(format!("Failed to list models: {}", e)),
-    };
-
-    let models: Vec<String> = updated_models.into_iter().map(|model| model.name).collect();
-    Ok(models)
-}
-
 #[tauri::command]
 pub async fn check_encryption_client(
     encryption_instance: State<'_, EncryptionClientInstance>
@@ -63,6 +53,11 @@ pub async fn get_username(app_handle: tauri::AppHandle) -> Result<String, String
     }
 }
 
+#[tauri::command]
+pub async fn get_platform(state: State<'_, GenerateState>) -> Result<PlatformInfo, String> {
+    Ok(state.get_platform().await)
+}
+
 #[tauri::command]
 pub async fn fetch_preferences(
     username: &str,
@@ -82,13 +77,52 @@ pub async fn fetch_full_json(state: State<'_, GenerateState>) -> Result<String,
 pub async fn execute_command(
     command: String,
     update: bool,
+    approved: Option<String>,
     app_handle: tauri::AppHandle,
     encryption_instance: State<'_, EncryptionClientInstance>,
     state: State<'_, GenerateState>,
 ) -> Result<(), String> {
+    // The source decides whether a change is recorded and held for confirmation, so
+    // it is never taken from the webview.
+    let source = ChangeSource::Chat;
+    let approved: Vec<String> = approved.into_iter().collect();
+    preview::check_approval(&state, &[(command.as_str(), approved.first().map(String::as_str))], &approved, *preview::REQUIRE_APPROVAL).await?;
+    let config: AppConfig = serde_json::from_str(&state.get_full_json().await).unwrap_or_default();
+    let (base_command, value) = preferences::split_command(&config, &state.get_platform_info().await, &command)
+        .ok_or_else(|| format!("Unrecognized/unauthorized command: '{}'. Will not execute command.", command))?;
     generation::execute_command_impl(
-        command,
+        base_command,
+        value,
+        update,
+        source,
+        app_handle,
+        encryption_instance,
+        state
+    ).await
+}
+
+#[tauri::command]
+pub async fn execute_actions(
+    actions: Vec<SettingAction>,
+    update: bool,
+    approved: Option<Vec<String>>,
+    app_handle: tauri::AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<(), String> {
+    let source = ChangeSource::Chat;
+    let commands: Vec<(&str, Option<&str>)> = actions
+        .iter()
+        .map(|action| (action.command.as_str(), action.preview.as_ref().map(|preview| preview.id.as_str())))
+        .collect();
+    // The approved actions are run as they were issued; the webview's copies only
+    // name which ones.
+    let issued = preview::check_approval(&state, &commands, &approved.unwrap_or_default(), *preview::REQUIRE_APPROVAL).await?;
+    let actions = issued.unwrap_or(actions);
+    generation::execute_actions_impl(
+        actions,
         update,
+        source,
         app_handle,
         encryption_instance,
         state
@@ -112,17 +146,44 @@ pub async fn execute_startup_app_command(
 pub async fn generate(
     request: ChatRequest,
     encryption_instance: State<'_, EncryptionClientInstance>,
-    g_ollama: State<'_, OllamaInstance>,
-    seen_chats: State<'_, ChatIDs>,
+    llm: State<'_, LlmInstance>,
+    histories: State<'_, ChatHistories>,
     app_handle: tauri::AppHandle,
     gen_state: State<'_, GenerateState>
 ) -> Result<GenerateResult, String> {
     generation::generate_impl(
         request,
         encryption_instance,
-        g_ollama,
-        seen_chats,
+        llm,
+        histories,
         app_handle,
         gen_state
     ).await
 }
+
+#[tauri::command]
+pub async fn generate_stream(
+    request: ChatRequest,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    llm: State<'_, LlmInstance>,
+    histories: State<'_, ChatHistories>,
+    app_handle: tauri::AppHandle,
+    gen_state: State<'_, GenerateState>
+) -> Result<GenerateResult, String> {
+    generation::generate_stream_impl(
+        request,
+        encryption_instance,
+        llm,
+        histories,
+        app_handle,
+        gen_state
+    ).await
+}
+
+#[tauri::command]
+pub async fn cancel_generation(
+    chat_id: String,
+    gen_state: State<'_, GenerateState>
+) -> Result<bool, String> {
+    Ok(gen_state.cancel_generation(&chat_id).await)
+}
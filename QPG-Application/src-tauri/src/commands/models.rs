@@ -0,0 +1,80 @@
+use tauri::{AppHandle, State};
+use crate::intent::{self, RulesMode};
+use crate::llm::DEFAULT_MODEL;
+use crate::model_manager;
+use crate::models::{ModelRecommendation, ModelStatus};
+use crate::state::{GenerateState, LlmInstance};
+
+// With no models installed, DEFAULT_MODEL is pulled in the background and the
+// rule-based parser is offered until it arrives; the frontend lists models again
+// when the pull's final "model-pull-progress" event comes in.
+#[tauri::command]
+pub async fn list_models(app_handle: AppHandle, llm: State<'_, LlmInstance>) -> Result<Vec<String>, String> {
+    if *intent::INTENT_RULES == RulesMode::Only {
+        return Ok(vec![intent::RULES_MODEL.to_string()]);
+    }
+    let local_models = match llm.0.list_models().await {
+        Ok(models) => models,
+        Err(e) => {
+            println!("[list_models] {}; offering the rule-based parser only", e);
+            return Ok(vec![intent::RULES_MODEL.to_string()]);
+        }
+    };
+    if local_models.is_empty() {
+        println!("[list_models] No local models found, pulling {} in the background", *DEFAULT_MODEL);
+        if let Err(e) = model_manager::start_pull(&app_handle, llm.0.clone(), &DEFAULT_MODEL).await {
+            println!("[list_models] {}", e);
+        }
+        return Ok(vec![intent::RULES_MODEL.to_string()]);
+    }
+    Ok(local_models)
+}
+
+// Returns as soon as the download has started.
+#[tauri::command]
+pub async fn pull_model(
+    model: Option<String>,
+    app_handle: AppHandle,
+    llm: State<'_, LlmInstance>,
+) -> Result<(), String> {
+    let model = model.filter(|model| !model.trim().is_empty()).unwrap_or_else(|| DEFAULT_MODEL.clone());
+    model_manager::start_pull(&app_handle, llm.0.clone(), model.trim()).await
+}
+
+#[tauri::command]
+pub async fn cancel_model_pull(model: String, gen_state: State<'_, GenerateState>) -> Result<bool, String> {
+    Ok(gen_state.cancel_pull(&model).await)
+}
+
+#[tauri::command]
+pub async fn delete_model(model: String, llm: State<'_, LlmInstance>) -> Result<(), String> {
+    llm.0.delete_model(&model).await?;
+    println!("[models] Deleted {}", model);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn recommend_model(llm: State<'_, LlmInstance>) -> Result<ModelRecommendation, String> {
+    Ok(model_manager::recommend_model(llm.0.as_ref()).await)
+}
+
+// Called when the user picks a model: it's remembered for the next launch and
+// loaded in the background. Progress arrives as "model-status" events.
+#[tauri::command]
+pub async fn warm_up_model(
+    model: String,
+    app_handle: AppHandle,
+    llm: State<'_, LlmInstance>,
+    gen_state: State<'_, GenerateState>,
+) -> Result<ModelStatus, String> {
+    if let Err(e) = model_manager::remember_selected_model(&app_handle, &model) {
+        println!("[models] {}", e);
+    }
+    model_manager::warm_up(&app_handle, llm.0.clone(), &model).await;
+    Ok(gen_state.get_model_status(&model).await)
+}
+
+#[tauri::command]
+pub async fn get_model_status(model: String, gen_state: State<'_, GenerateState>) -> Result<ModelStatus, String> {
+    Ok(gen_state.get_model_status(&model).await)
+}
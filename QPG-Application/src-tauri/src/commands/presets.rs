@@ -0,0 +1,50 @@
+use tauri::{AppHandle, State};
+use crate::models::{ChangeSource, Preset, SettingDiff};
+use crate::{presets, profiles};
+use crate::state::{EncryptionClientInstance, GenerateState};
+
+#[tauri::command]
+pub async fn list_presets(state: State<'_, GenerateState>) -> Result<Vec<Preset>, String> {
+    Ok(presets::load_presets(&state).await)
+}
+
+#[tauri::command]
+pub async fn reload_presets(state: State<'_, GenerateState>) -> Result<Vec<Preset>, String> {
+    Ok(presets::reload_presets(&state).await)
+}
+
+#[tauri::command]
+pub async fn preview_preset(
+    id: String,
+    app_handle: AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<Vec<SettingDiff>, String> {
+    let preset = find_preset(&id, &state).await?;
+    let config = profiles::current_config(&app_handle, &encryption_instance, &state).await?;
+    Ok(profiles::diff_values(&config, &state.get_platform_info().await, &preset.values))
+}
+
+#[tauri::command]
+pub async fn apply_preset(
+    id: String,
+    app_handle: AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<Vec<SettingDiff>, String> {
+    let preset = find_preset(&id, &state).await?;
+    let env = state.get_platform_info().await;
+    let config = profiles::current_config(&app_handle, &encryption_instance, &state).await?;
+    let diffs = profiles::diff_values(&config, &env, &preset.values);
+    println!("[presets] Applying '{}' v{}: {} setting(s) differ", preset.id, preset.version, diffs.len());
+
+    profiles::apply_diffs(diffs, &config, &env, ChangeSource::Preset, &app_handle, &encryption_instance, &state).await
+}
+
+async fn find_preset(id: &str, state: &GenerateState) -> Result<Preset, String> {
+    presets::load_presets(state)
+        .await
+        .into_iter()
+        .find(|preset| preset.id == id)
+        .ok_or_else(|| format!("No preset with id '{}'", id))
+}
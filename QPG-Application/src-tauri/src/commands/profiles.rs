@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use tauri::{AppHandle, State};
+use crate::models::{ChangeSource, DefaultValue, Profile, SettingDiff};
+use crate::profiles;
+use crate::state::{EncryptionClientInstance, GenerateState};
+
+#[tauri::command]
+pub async fn list_profiles(
+    app_handle: AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<Vec<Profile>, String> {
+    let username = state.get_username(&app_handle).await;
+    let mut profiles: Vec<Profile> = profiles::load_profiles(&username, &encryption_instance, &state)
+        .await?
+        .into_values()
+        .collect();
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(profiles)
+}
+
+#[tauri::command]
+pub async fn get_active_profile(state: State<'_, GenerateState>) -> Result<Option<String>, String> {
+    Ok(state.get_active_profile().await)
+}
+
+#[tauri::command]
+pub async fn create_profile(
+    name: String,
+    values: Option<HashMap<String, DefaultValue>>,
+    app_handle: AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<Profile, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Profile name cannot be empty".to_string());
+    }
+
+    let username = state.get_username(&app_handle).await;
+    let mut all_profiles = profiles::load_profiles(&username, &encryption_instance, &state).await?;
+    if all_profiles.contains_key(&name) {
+        return Err(format!("Profile '{}' already exists", name));
+    }
+
+    let values = match values {
+        Some(values) => values,
+        None => {
+            let config = profiles::current_config(&app_handle, &encryption_instance, &state).await?;
+            profiles::snapshot_values(&config, &state.get_platform_info().await)
+        }
+    };
+
+    let profile = Profile { name: name.clone(), values };
+    all_profiles.insert(name, profile.clone());
+    profiles::save_profiles(&username, all_profiles, &encryption_instance, &state).await?;
+    Ok(profile)
+}
+
+#[tauri::command]
+pub async fn rename_profile(
+    name: String,
+    new_name: String,
+    app_handle: AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<(), String> {
+    let new_name = new_name.trim().to_string();
+    if new_name.is_empty() {
+        return Err("Profile name cannot be empty".to_string());
+    }
+
+    let username = state.get_username(&app_handle).await;
+    let mut all_profiles = profiles::load_profiles(&username, &encryption_instance, &state).await?;
+    if all_profiles.contains_key(&new_name) {
+        return Err(format!("Profile '{}' already exists", new_name));
+    }
+    let mut profile = all_profiles
+        .remove(&name)
+        .ok_or_else(|| format!("No profile named '{}'", name))?;
+
+    profile.name = new_name.clone();
+    all_profiles.insert(new_name.clone(), profile);
+    profiles::save_profiles(&username, all_profiles, &encryption_instance, &state).await?;
+
+    if state.get_active_profile().await.as_deref() == Some(name.as_str()) {
+        state.set_active_profile(Some(new_name)).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_profile(
+    name: String,
+    app_handle: AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<(), String> {
+    let username = state.get_username(&app_handle).await;
+    let mut all_profiles = profiles::load_profiles(&username, &encryption_instance, &state).await?;
+    if all_profiles.remove(&name).is_none() {
+        return Err(format!("No profile named '{}'", name));
+    }
+    profiles::save_profiles(&username, all_profiles, &encryption_instance, &state).await?;
+
+    if state.get_active_profile().await.as_deref() == Some(name.as_str()) {
+        state.set_active_profile(None).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn diff_profile(
+    name: String,
+    app_handle: AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<Vec<SettingDiff>, String> {
+    let username = state.get_username(&app_handle).await;
+    let all_profiles = profiles::load_profiles(&username, &encryption_instance, &state).await?;
+    let profile = all_profiles
+        .get(&name)
+        .ok_or_else(|| format!("No profile named '{}'", name))?;
+
+    let config = profiles::current_config(&app_handle, &encryption_instance, &state).await?;
+    Ok(profiles::diff_values(&config, &state.get_platform_info().await, &profile.values))
+}
+
+#[tauri::command]
+pub async fn activate_profile(
+    name: String,
+    app_handle: AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<Vec<SettingDiff>, String> {
+    let username = state.get_username(&app_handle).await;
+    let all_profiles = profiles::load_profiles(&username, &encryption_instance, &state).await?;
+    let profile = all_profiles
+        .get(&name)
+        .ok_or_else(|| format!("No profile named '{}'", name))?;
+
+    let env = state.get_platform_info().await;
+    let config = profiles::current_config(&app_handle, &encryption_instance, &state).await?;
+    let diffs = profiles::diff_values(&config, &env, &profile.values);
+    println!("[profiles] Activating '{}': {} setting(s) differ", name, diffs.len());
+
+    let applied = profiles::apply_diffs(diffs, &config, &env, ChangeSource::Profile, &app_handle, &encryption_instance, &state).await?;
+    state.set_active_profile(Some(name)).await;
+    Ok(applied)
+}
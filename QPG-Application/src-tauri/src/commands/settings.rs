@@ -0,0 +1,19 @@
+use tauri::{AppHandle, State};
+use crate::models::SettingReading;
+use crate::{profiles, settings};
+use crate::state::{EncryptionClientInstance, GenerateState};
+
+#[tauri::command]
+pub async fn read_live_settings(
+    keys: Option<Vec<String>>,
+    app_handle: AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+) -> Result<Vec<SettingReading>, String> {
+    let env = state.get_platform_info().await;
+    let config = profiles::current_config(&app_handle, &encryption_instance, &state).await?;
+    let mut keys = keys.unwrap_or_else(|| profiles::snapshot_values(&config, &env).into_keys().collect());
+    keys.sort();
+
+    Ok(settings::read_settings(&app_handle, &config, &env, &keys).await)
+}
@@ -2,7 +2,7 @@ use tauri::AppHandle;
 use tauri::State;
 use crate::preferences;
 use crate::state::{EncryptionClientInstance, GenerateState};
-use crate::models::{DefaultValue, Setting};
+use crate::models::{ChangeSource, Setting};
 use serde_json::Value;
 use std::time::Duration;
 
@@ -33,28 +33,18 @@ pub async fn init_startup_commands(
     if let Value::Object(obj) = parsed {
         for (_, setting_value) in obj.iter() {
             if let Ok(setting) = serde_json::from_value::<Setting>(setting_value.clone()) {
-                let command_str = match platform_info.as_str() {
-                    "windows" => setting.commands.windows.clone(),
-                    "macos" => setting.commands.macos.clone(),
-                    s if s.contains("gnome") => setting.commands.gnome.clone(),
-                    _ => "".to_string(),
-                };
-                if command_str.trim().is_empty() {
+                let command_str = preferences::command_for_env(&setting, &platform_info);
+                if command_str.is_empty() {
                     continue;
                 }
 
-                let value_str = match &setting.current {
-                    DefaultValue::Bool(b) => b.to_string(),
-                    DefaultValue::Float(f) => f.to_string(),
-                    DefaultValue::String(s) => s.to_string(),
-                };
-
-                let full_command = format!("{} {}", command_str.trim(), value_str);
-                println!("[startup_init] Executing: {}", full_command);
+                println!("[startup_init] Executing: {} {}", command_str, setting.current);
 
                 if let Err(e) = super::generation::execute_command_impl(
-                    full_command,
+                    command_str,
+                    setting.current.to_string(),
                     false,
+                    ChangeSource::Startup,
                     app_handle.clone(),
                     encryption_instance.clone(),
                     state.clone(),
@@ -0,0 +1,198 @@
+use std::env;
+use std::time::Duration;
+use once_cell::sync::Lazy;
+use tauri::{AppHandle, Emitter, Manager, State};
+use crate::models::{ChangeSource, DefaultValue, PendingChange, Setting};
+use crate::preferences::{find_setting_for_command, update_json_current_value};
+use crate::settings;
+use crate::state::{EncryptionClientInstance, GenerateState};
+
+// Anything shorter wouldn't leave time to read the prompt.
+pub static CONFIRM_TIMEOUT_SECS: Lazy<u64> = Lazy::new(|| {
+    env::var("CONFIRM_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|secs| *secs >= 5)
+        .unwrap_or(15)
+});
+pub static RISKY_CHANGE_FRACTION: Lazy<f32> = Lazy::new(|| {
+    env::var("RISKY_CHANGE_FRACTION")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0.25)
+});
+
+// A change is provisional when the catalog entry asks for it, or when a numeric
+// setting jumps by more than RISKY_CHANGE_FRACTION of its allowed range. Changes
+// that restore earlier state (startup, undo, restore) are never held.
+pub fn needs_confirmation(setting: &Setting, new_value: &DefaultValue, source: ChangeSource) -> bool {
+    if !matches!(source, ChangeSource::Chat | ChangeSource::Profile | ChangeSource::Preset) {
+        return false;
+    }
+    if setting.requires_confirmation {
+        return true;
+    }
+
+    match (&setting.current, new_value, setting.lower_bound, setting.upper_bound) {
+        (DefaultValue::Float(old), DefaultValue::Float(new), Some(lower), Some(upper)) if upper > lower => {
+            (new - old).abs() / (upper - lower) > *RISKY_CHANGE_FRACTION
+        }
+        _ => false,
+    }
+}
+
+pub async fn hold_for_confirmation(
+    app_handle: &AppHandle,
+    state: &GenerateState,
+    pending: PendingChange,
+) -> PendingChange {
+    state.add_pending_change(pending.clone()).await;
+    if let Err(e) = app_handle.emit("change-pending-confirmation", &pending) {
+        println!("[confirmation] Failed to emit pending change event: {}", e);
+    }
+    schedule_revert(app_handle.clone(), pending.id.clone(), pending.timeout_secs);
+    pending
+}
+
+fn schedule_revert(app_handle: AppHandle, id: String, timeout_secs: u64) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(timeout_secs)).await;
+
+        let state = app_handle.state::<GenerateState>();
+        if let Some(pending) = state.take_pending_change(&id).await {
+            println!("[confirmation] No confirmation for {} within {}s, reverting", id, timeout_secs);
+            if let Err(e) = revert_pending(&app_handle, &pending).await {
+                println!("[confirmation] Failed to revert {}: {}", id, e);
+            }
+        }
+    });
+}
+
+// Records a held change as made and saves it as the user's preference.
+pub async fn keep_pending(
+    app_handle: &AppHandle,
+    encryption_instance: State<'_, EncryptionClientInstance>,
+    state: State<'_, GenerateState>,
+    pending: PendingChange,
+) -> Result<(), String> {
+    state.record_change(
+        &pending.setting,
+        &pending.command,
+        Some(pending.old_value.clone()),
+        pending.new_value.clone(),
+        pending.source,
+    ).await;
+    if let Err(e) = app_handle.emit("change-confirmed", &pending) {
+        println!("[confirmation] Failed to emit confirm event: {}", e);
+    }
+
+    if pending.update {
+        let username = state.get_username(app_handle).await;
+        update_json_current_value(
+            &username,
+            &pending.command,
+            &pending.new_value.to_string(),
+            encryption_instance,
+            state,
+        ).await?;
+    }
+    Ok(())
+}
+
+// A newer change to the same setting replaces any change still waiting for
+// confirmation, so the old change's timer can't put back a value from before it.
+pub async fn supersede_pending(app_handle: &AppHandle, state: &GenerateState, setting: &str) {
+    for pending in take_superseded(state, setting).await {
+        println!("[confirmation] Change {} to '{}' was replaced by a newer change", pending.id, setting);
+        if let Err(e) = app_handle.emit("change-superseded", &pending) {
+            println!("[confirmation] Failed to emit superseded event: {}", e);
+        }
+    }
+}
+
+async fn take_superseded(state: &GenerateState, setting: &str) -> Vec<PendingChange> {
+    let mut superseded = Vec::new();
+    while let Some(pending) = state.take_pending_change_for_setting(setting).await {
+        superseded.push(pending);
+    }
+    superseded
+}
+
+// Only puts the old value back while the held one is still in place. When the live
+// value can't be read, the revert goes ahead.
+pub async fn revert_pending(app_handle: &AppHandle, pending: &PendingChange) -> Result<(), String> {
+    let state = app_handle.state::<GenerateState>();
+    let env = state.get_platform_info().await;
+    if let Some((_, setting)) = find_setting_for_command(&state.get_full_json().await, &env, &pending.command) {
+        let live = settings::read_live_value(app_handle, &setting, &env).await.ok();
+        if !still_applied(pending, live.as_ref()) {
+            println!("[confirmation] '{}' changed since {} was held, not reverting", pending.setting, pending.id);
+            if let Err(e) = app_handle.emit("change-superseded", pending) {
+                println!("[confirmation] Failed to emit superseded event: {}", e);
+            }
+            return Ok(());
+        }
+    }
+
+    crate::commands::generation::run_setting_command(
+        app_handle,
+        &pending.command,
+        &pending.old_value.to_string(),
+    ).await?;
+
+    if let Err(e) = app_handle.emit("change-reverted", pending) {
+        println!("[confirmation] Failed to emit revert event: {}", e);
+    }
+    Ok(())
+}
+
+fn still_applied(pending: &PendingChange, live: Option<&DefaultValue>) -> bool {
+    match (live, &pending.new_value) {
+        (None, _) => true,
+        (Some(DefaultValue::Float(live)), DefaultValue::Float(new)) => (live - new).abs() < 1e-4,
+        (Some(live), new) => live == new,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(setting: &str, old_value: f32, new_value: f32) -> PendingChange {
+        PendingChange {
+            id: uuid::Uuid::new_v4().to_string(),
+            setting: setting.to_string(),
+            command: "gsettings set org.gnome.desktop.interface text-scaling-factor".to_string(),
+            old_value: DefaultValue::Float(old_value),
+            new_value: DefaultValue::Float(new_value),
+            source: ChangeSource::Chat,
+            timeout_secs: 15,
+            update: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn a_newer_change_stops_the_held_one_from_reverting() {
+        let state = GenerateState::default();
+        let risky = held("zoom", 1.0, 3.0);
+        state.add_pending_change(risky.clone()).await;
+        state.add_pending_change(held("cursor_size", 24.0, 96.0)).await;
+
+        // A second change to zoom is applied before the first one times out.
+        let superseded = take_superseded(&state, "zoom").await;
+        assert_eq!(superseded.len(), 1);
+        assert_eq!(superseded[0].id, risky.id);
+
+        // The timer then finds nothing to revert, and other settings are left alone.
+        assert!(state.take_pending_change(&risky.id).await.is_none());
+        assert_eq!(state.get_pending_changes().await.len(), 1);
+    }
+
+    #[test]
+    fn reverts_only_while_the_held_value_is_live() {
+        let risky = held("zoom", 1.0, 3.0);
+        assert!(still_applied(&risky, Some(&DefaultValue::Float(3.0))));
+        assert!(still_applied(&risky, None));
+        assert!(!still_applied(&risky, Some(&DefaultValue::Float(1.5))));
+    }
+}
@@ -0,0 +1,295 @@
+use std::env;
+use once_cell::sync::Lazy;
+use serde_json::{Map, Value};
+use crate::llm::{ChatMessage, LlmBackend, LlmRequest, MessageRole};
+use crate::state::GenerateState;
+
+// Upper bound on the window we ask the model for, whatever the model was trained with.
+// Larger windows cost memory on every request.
+pub static MAX_CONTEXT_TOKENS: Lazy<usize> = Lazy::new(|| {
+    env::var("MAX_CONTEXT_TOKENS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|n| *n >= 1024)
+        .unwrap_or(8192)
+});
+
+// Ollama's own default when a model doesn't report its context length.
+const FALLBACK_CONTEXT_TOKENS: usize = 2048;
+// Share of the window the reference catalog may take up in the system prompt.
+const CATALOG_SHARE: f64 = 0.4;
+// Kept free for the model's reply.
+const REPLY_RESERVE_TOKENS: usize = 512;
+// The latest turns are never summarized, so follow-ups keep their exact wording.
+const KEEP_RECENT_MESSAGES: usize = 4;
+
+const USER_MARKER: &str = "User request: ";
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation: ";
+const SUMMARY_INSTRUCTIONS: &str = "Summarize this conversation between a user and an accessibility assistant in at most five sentences. Keep every setting that was discussed or changed and the values it was set to. Reply with the summary only.";
+
+// Rough count that errs on the high side: about four characters per token plus a
+// few for each message's framing.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+fn message_tokens(message: &ChatMessage) -> usize {
+    let calls = if message.tool_calls.is_empty() {
+        0
+    } else {
+        estimate_tokens(&serde_json::to_string(&message.tool_calls).unwrap_or_default())
+    };
+    estimate_tokens(&message.content) + calls + 4
+}
+
+// The window used for every request in a chat with `model`: what the backend reports
+// for the model, capped at MAX_CONTEXT_TOKENS. Looked up once per model.
+pub async fn context_window(llm: &dyn LlmBackend, state: &GenerateState, model: &str) -> usize {
+    if let Some(window) = state.get_context_window(model).await {
+        return window;
+    }
+
+    let window = match llm.context_length(model).await {
+        Ok(Some(length)) => length.min(*MAX_CONTEXT_TOKENS),
+        Ok(None) => FALLBACK_CONTEXT_TOKENS,
+        Err(e) => {
+            println!("[context] Could not read the context length of {}: {}", model, e);
+            FALLBACK_CONTEXT_TOKENS
+        }
+    };
+    println!("[context] Using a {} token window for {}", window, model);
+    state.set_context_window(model, window).await;
+    window
+}
+
+// Fits the catalog into its share of the window. Descriptions and other matching
+// hints go first, then whole settings from the end; the setting that matters for
+// each message is sent alongside it anyway.
+pub fn fit_catalog(catalog_json: &str, window: usize) -> String {
+    let budget = (window as f64 * CATALOG_SHARE) as usize;
+    if estimate_tokens(catalog_json) <= budget {
+        return catalog_json.to_string();
+    }
+    let Ok(Value::Object(root)) = serde_json::from_str::<Value>(catalog_json) else {
+        return catalog_json.to_string();
+    };
+
+    let mut compact: Vec<(String, Value)> = root
+        .into_iter()
+        .map(|(key, mut setting)| {
+            if let Value::Object(fields) = &mut setting {
+                for field in ["display_name", "description", "synonyms", "translations", "platforms"] {
+                    fields.remove(field);
+                }
+            }
+            (key, setting)
+        })
+        .collect();
+
+    let render = |settings: &[(String, Value)]| {
+        serde_json::to_string(&settings.iter().cloned().collect::<Map<String, Value>>()).unwrap_or_default()
+    };
+    let total = compact.len();
+    while compact.len() > 1 && estimate_tokens(&render(&compact)) > budget {
+        compact.pop();
+    }
+    if compact.len() < total {
+        println!("[context] Catalog trimmed to {} of {} settings to fit the window", compact.len(), total);
+    }
+    render(&compact)
+}
+
+// The request as it goes into a user turn. Everything before the marker is treated as
+// the turn's reference snippet and dropped from older turns.
+pub fn marked_prompt(prompt: &str) -> String {
+    format!("{}{}", USER_MARKER, prompt)
+}
+
+fn without_reference(content: &str) -> String {
+    match content.rfind(USER_MARKER) {
+        Some(index) => content[index + USER_MARKER.len()..].to_string(),
+        None => content.to_string(),
+    }
+}
+
+// Brings a chat's history within the window, leaving room for the next prompt and the
+// reply. System messages are never touched. Older turns first lose their reference
+// snippets, then are folded into a summary, and as a last resort are dropped.
+pub async fn fit_history(
+    llm: &dyn LlmBackend,
+    model: &str,
+    chat_id: &str,
+    history: &mut Vec<ChatMessage>,
+    window: usize,
+    next_prompt: &str,
+) {
+    let budget = window.saturating_sub(estimate_tokens(next_prompt) + REPLY_RESERVE_TOKENS);
+    let total = |messages: &[ChatMessage]| messages.iter().map(message_tokens).sum::<usize>();
+    if total(history) <= budget {
+        return;
+    }
+
+    let (pinned, mut turns): (Vec<ChatMessage>, Vec<ChatMessage>) = history
+        .drain(..)
+        .partition(|message| message.role == MessageRole::System && !message.content.starts_with(SUMMARY_PREFIX));
+    let previous_summary = history_summary(&mut turns);
+
+    let recent_from = turn_start(&turns, turns.len().saturating_sub(KEEP_RECENT_MESSAGES));
+    for message in &mut turns[..recent_from] {
+        if message.role == MessageRole::User {
+            message.content = without_reference(&message.content);
+        }
+    }
+
+    let mut summary = previous_summary;
+    let fits = |pinned: &[ChatMessage], summary: &Option<String>, turns: &[ChatMessage]| {
+        let summary_tokens = summary.as_deref().map_or(0, |s| estimate_tokens(s) + 4);
+        total(pinned) + summary_tokens + total(turns) <= budget
+    };
+
+    if !fits(&pinned, &summary, &turns) && recent_from > 0 {
+        let older: Vec<ChatMessage> = turns.drain(..recent_from).collect();
+        match summarize(llm, model, summary.as_deref(), &older, window).await {
+            Ok(text) => {
+                println!("[context] Summarized {} earlier message(s) in chat {}", older.len(), chat_id);
+                summary = Some(text);
+            }
+            Err(e) => println!("[context] Summarizing chat {} failed, dropping older turns: {}", chat_id, e),
+        }
+    }
+    while !fits(&pinned, &summary, &turns) && turns.len() > 1 {
+        turns.remove(0);
+        while turns.first().is_some_and(|message| message.role == MessageRole::Tool) {
+            turns.remove(0);
+        }
+    }
+
+    history.extend(pinned);
+    if let Some(summary) = summary {
+        history.push(ChatMessage::system(format!("{}{}", SUMMARY_PREFIX, summary)));
+    }
+    history.extend(turns);
+}
+
+// Tool results are kept with the assistant message that called the tools; servers
+// refuse a tool result without its call.
+fn turn_start(turns: &[ChatMessage], mut index: usize) -> usize {
+    while index > 0 && turns.get(index).is_some_and(|message| message.role == MessageRole::Tool) {
+        index -= 1;
+    }
+    index
+}
+
+fn history_summary(turns: &mut Vec<ChatMessage>) -> Option<String> {
+    let index = turns
+        .iter()
+        .position(|message| message.role == MessageRole::System && message.content.starts_with(SUMMARY_PREFIX))?;
+    let message = turns.remove(index);
+    Some(message.content[SUMMARY_PREFIX.len()..].to_string())
+}
+
+// The transcript is sent in chunks that fit the window alongside the instructions and
+// the reply, each summarized together with the summary of the chunks before it. A
+// single message too long for a chunk is cut short.
+async fn summarize(
+    llm: &dyn LlmBackend,
+    model: &str,
+    previous: Option<&str>,
+    messages: &[ChatMessage],
+    window: usize,
+) -> Result<String, String> {
+    let budget = window.saturating_sub(estimate_tokens(SUMMARY_INSTRUCTIONS) + REPLY_RESERVE_TOKENS + 8);
+    let mut summary = previous.map(str::to_string);
+    let mut lines = messages.iter().map(transcript_line).peekable();
+
+    loop {
+        let mut transcript = summary
+            .as_deref()
+            .map(|summary| format!("Earlier summary: {}\n", summary))
+            .unwrap_or_default();
+        let mut added = 0;
+        while let Some(line) = lines.peek() {
+            let room = budget.saturating_sub(estimate_tokens(&transcript));
+            if estimate_tokens(line) > room {
+                if added > 0 {
+                    break;
+                }
+                transcript.extend(line.chars().take(room * 4));
+                transcript.push('\n');
+            } else {
+                transcript.push_str(line);
+            }
+            lines.next();
+            added += 1;
+        }
+
+        summary = Some(summarize_chunk(llm, model, transcript, window).await?);
+        if lines.peek().is_none() {
+            break;
+        }
+    }
+    summary.ok_or_else(|| "Nothing to summarize".to_string())
+}
+
+fn transcript_line(message: &ChatMessage) -> String {
+    let speaker = match message.role {
+        MessageRole::User => "User",
+        MessageRole::Tool => "Tool result",
+        _ => "Assistant",
+    };
+    format!("{}: {}\n", speaker, message.content)
+}
+
+async fn summarize_chunk(llm: &dyn LlmBackend, model: &str, transcript: String, window: usize) -> Result<String, String> {
+    let request = LlmRequest::new(
+        model,
+        vec![
+            ChatMessage::system(SUMMARY_INSTRUCTIONS.to_string()),
+            ChatMessage::user(transcript),
+        ],
+        window,
+    );
+    let response = llm
+        .chat(&request)
+        .await
+        .map_err(|e| format!("Failed to summarize: {}", e))?;
+
+    let text = response.message.content.trim().to_string();
+    if text.is_empty() {
+        return Err("The model returned an empty summary".to_string());
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::fake::{FakeBackend, FakeReply};
+
+    const WINDOW: usize = 1024;
+
+    #[tokio::test]
+    async fn long_transcripts_are_summarized_in_chunks_that_fit() {
+        let llm = FakeBackend::default();
+        for _ in 0..10 {
+            llm.push_reply(FakeReply::text("The user made the text bigger."));
+        }
+        let messages: Vec<ChatMessage> = (0..12)
+            .map(|i| ChatMessage::user(format!("message {} {}", i, "word ".repeat(120))))
+            .chain([ChatMessage::assistant("x".repeat(8000))])
+            .collect();
+
+        let summary = summarize(&llm, "fake", Some("Earlier."), &messages, WINDOW).await.unwrap();
+        assert_eq!(summary, "The user made the text bigger.");
+
+        let requests = llm.requests();
+        assert!(requests.len() > 1);
+        for request in &requests {
+            let sent: usize = request.messages.iter().map(|message| estimate_tokens(&message.content)).sum();
+            assert!(sent + REPLY_RESERVE_TOKENS <= WINDOW, "{} tokens sent", sent);
+        }
+        assert!(requests[0].messages[1].content.starts_with("Earlier summary: Earlier."));
+        assert!(requests[1].messages[1].content.starts_with("Earlier summary: The user made the text bigger."));
+        assert!(requests.last().unwrap().messages[1].content.contains("Assistant: xxx"));
+    }
+}
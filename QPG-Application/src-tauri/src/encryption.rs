@@ -136,35 +136,58 @@ impl EncryptionClient {
     }
 
     pub fn encrypt_data(&self, data: &str) -> Result<EncryptedData, String> {
-        let key = Key::<Aes256Gcm>::from_slice(&self.shared_secret);
-        let cipher = Aes256Gcm::new(key);
-        let nonce = Aes256Gcm::generate_nonce(OsRng);
-        let ciphertext = cipher.encrypt(&nonce, data.as_bytes())
-            .map_err(|e| format!("Encryption error: {}", e))?;
-        let nonce_b64 = BASE64_STANDARD.encode(&nonce);
+        let sealed = encrypt_with_key(&self.shared_secret, data)?;
         Ok(EncryptedData {
-            ciphertext_b64: BASE64_STANDARD.encode(&ciphertext),
-            nonce_b64,
+            ciphertext_b64: sealed.ciphertext_b64,
+            nonce_b64: sealed.nonce_b64,
             client_id: self.client_id.clone()
         })
     }
 
     pub fn decrypt_data(&self, data: DecryptData) -> Result<String, String> {
-        let key = Key::<Aes256Gcm>::from_slice(&self.shared_secret);
-        let cipher = Aes256Gcm::new(&key);
-        let nonce_vec = BASE64_STANDARD.decode(&data.nonce_b64)
-            .map_err(|e| format!("Failed to decode nonce: {}", e))?;
-        let ciphertext = BASE64_STANDARD.decode(&data.ciphertext_b64)
-            .map_err(|e| format!("Failed to decode ciphertext: {}", e))?;
-        
-        let nonce = Nonce::from_slice(&nonce_vec);
-        let plaintext = cipher.decrypt(nonce, ciphertext.as_ref())
-            .map_err(|e| format!("Decryption error: {}", e))?;
-        
-        let plaintext_str = String::from_utf8(plaintext)
-            .map_err(|e| format!("Invalid UTF-8: {}", e))?;
-            
-        Ok(plaintext_str)
+        decrypt_with_key(&self.shared_secret, &data)
     }
 
 }
+
+// AES-256-GCM with a fresh nonce per message. `key` must be 32 bytes.
+pub fn encrypt_with_key(key: &[u8], data: &str) -> Result<DecryptData, String> {
+    if key.len() != 32 {
+        return Err("Encryption key must be 32 bytes".to_string());
+    }
+    let key = Key::<Aes256Gcm>::from_slice(key);
+    let cipher = Aes256Gcm::new(key);
+    let nonce = Aes256Gcm::generate_nonce(OsRng);
+    let ciphertext = cipher.encrypt(&nonce, data.as_bytes())
+        .map_err(|e| format!("Encryption error: {}", e))?;
+    Ok(DecryptData {
+        ciphertext_b64: BASE64_STANDARD.encode(&ciphertext),
+        nonce_b64: BASE64_STANDARD.encode(&nonce),
+    })
+}
+
+pub fn decrypt_with_key(key: &[u8], data: &DecryptData) -> Result<String, String> {
+    if key.len() != 32 {
+        return Err("Encryption key must be 32 bytes".to_string());
+    }
+    let key = Key::<Aes256Gcm>::from_slice(key);
+    let cipher = Aes256Gcm::new(key);
+    let nonce_vec = BASE64_STANDARD.decode(&data.nonce_b64)
+        .map_err(|e| format!("Failed to decode nonce: {}", e))?;
+    let ciphertext = BASE64_STANDARD.decode(&data.ciphertext_b64)
+        .map_err(|e| format!("Failed to decode ciphertext: {}", e))?;
+    if nonce_vec.len() != 12 {
+        return Err("Nonce must be 12 bytes".to_string());
+    }
+
+    let nonce = Nonce::from_slice(&nonce_vec);
+    let plaintext = cipher.decrypt(nonce, ciphertext.as_ref())
+        .map_err(|e| format!("Decryption error: {}", e))?;
+
+    String::from_utf8(plaintext)
+        .map_err(|e| format!("Invalid UTF-8: {}", e))
+}
+
+pub fn generate_key() -> Vec<u8> {
+    Aes256Gcm::generate_key(OsRng).to_vec()
+}
@@ -0,0 +1,239 @@
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::commands::generation;
+use crate::intent;
+use crate::llm::LlmBackend;
+use crate::locale::Language;
+use crate::models::{ChatRequest, DefaultValue, PlatformInfo};
+use crate::preferences::filter_json_by_env;
+use crate::retrieval::{self, MATCH_TOP_K};
+use crate::state::GenerateState;
+
+// Prefix of the error generate returns when the model's reply can't be read.
+const PARSE_FAILURE: &str = "Failed to parse model response";
+// Floats within this of the expected value count as correct.
+const VALUE_TOLERANCE: f64 = 0.01;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EvalCase {
+    pub prompt: String,
+    #[serde(default)]
+    pub locale: Option<String>,
+    pub expected_setting: String,
+    // Left out for requests that shouldn't change anything, like questions.
+    #[serde(default)]
+    pub expected_value: Option<Value>,
+    // Replies the fake backend gives for this case, in order.
+    #[serde(default)]
+    pub fake_replies: Vec<String>,
+}
+
+pub struct EvalOptions {
+    pub model: String,
+    pub env: String,
+    pub catalog: String,
+    pub rule_fallback: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EvalAction {
+    pub setting: String,
+    pub value: DefaultValue,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseResult {
+    pub prompt: String,
+    pub expected_setting: String,
+    pub expected_value: Option<Value>,
+    pub retrieved: Vec<String>,
+    pub top1: bool,
+    pub top_k: bool,
+    pub answered_by: String,
+    pub actions: Vec<EvalAction>,
+    pub setting_correct: bool,
+    pub value_correct: Option<bool>,
+    pub parse_failure: bool,
+    pub error: Option<String>,
+    pub retrieval_ms: f64,
+    pub generate_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencySummary {
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EvalSummary {
+    pub cases: usize,
+    pub top1_accuracy: f64,
+    pub top_k_accuracy: f64,
+    pub setting_accuracy: f64,
+    // Over the cases that have an expected value.
+    pub value_accuracy: Option<f64>,
+    pub parse_failure_rate: f64,
+    pub error_rate: f64,
+    pub answered_by_rules: usize,
+    pub retrieval_latency: LatencySummary,
+    pub generate_latency: LatencySummary,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EvalReport {
+    pub backend: String,
+    pub model: String,
+    pub env: String,
+    pub top_k: usize,
+    pub rule_fallback: bool,
+    pub summary: EvalSummary,
+    pub results: Vec<CaseResult>,
+}
+
+pub struct Evaluator {
+    state: GenerateState,
+    filtered_json: String,
+    options: EvalOptions,
+}
+
+impl Evaluator {
+    pub async fn new(options: EvalOptions) -> Result<Self, String> {
+        let filtered_json = filter_json_by_env(&options.catalog, &options.env)
+            .map_err(|e| format!("Failed to read the catalog: {}", e))?;
+        let state = GenerateState::default();
+        state.set_platform(PlatformInfo { env: options.env.clone(), ..Default::default() });
+        state.update_jsons(&options.catalog, &filtered_json).await;
+        Ok(Self { state, filtered_json, options })
+    }
+
+    // Each case starts a fresh chat against the unchanged catalog, so results don't
+    // depend on the order of the corpus.
+    pub async fn run_case(&self, llm: &dyn LlmBackend, index: usize, case: &EvalCase) -> CaseResult {
+        let language = case.locale.as_deref().map(Language::from_locale).unwrap_or_default();
+
+        let started = Instant::now();
+        let ranking = retrieval::rank(None, llm, &self.state, &case.prompt, &self.filtered_json, language).await;
+        let retrieval_ms = elapsed_ms(started);
+        let retrieved: Vec<String> = ranking.matches.into_iter().map(|m| m.setting).collect();
+
+        let request = ChatRequest {
+            model: self.options.model.clone(),
+            prompt: case.prompt.clone(),
+            chat_id: format!("eval-{}", index),
+            setting: None,
+            locale: case.locale.clone(),
+        };
+        let started = Instant::now();
+        let reply = generation::headless_reply(&request, llm, &mut Vec::new(), &self.state, self.options.rule_fallback).await;
+        let generate_ms = elapsed_ms(started);
+
+        let (answered_by, actions, error) = match reply {
+            Ok(result) => {
+                let actions = result
+                    .actions
+                    .into_iter()
+                    .map(|action| EvalAction { setting: action.setting, value: action.value })
+                    .collect();
+                (result.ollama_response.model, actions, None)
+            }
+            Err(e) => (String::new(), Vec::new(), Some(e)),
+        };
+
+        let action = actions.iter().find(|action: &&EvalAction| action.setting == case.expected_setting);
+        let value_correct = case
+            .expected_value
+            .as_ref()
+            .map(|expected| action.is_some_and(|action| value_matches(&action.value, expected)));
+        // A question is answered correctly by changing nothing.
+        let setting_correct = match case.expected_value {
+            Some(_) => action.is_some(),
+            None => error.is_none() && actions.is_empty(),
+        };
+
+        CaseResult {
+            prompt: case.prompt.clone(),
+            expected_setting: case.expected_setting.clone(),
+            expected_value: case.expected_value.clone(),
+            top1: retrieved.first() == Some(&case.expected_setting),
+            top_k: retrieved.contains(&case.expected_setting),
+            retrieved,
+            answered_by,
+            actions,
+            setting_correct,
+            value_correct,
+            parse_failure: error.as_deref().is_some_and(|e| e.starts_with(PARSE_FAILURE)),
+            error,
+            retrieval_ms,
+            generate_ms,
+        }
+    }
+
+    pub fn report(&self, backend: &str, results: Vec<CaseResult>) -> EvalReport {
+        EvalReport {
+            backend: backend.to_string(),
+            model: self.options.model.clone(),
+            env: self.options.env.clone(),
+            top_k: *MATCH_TOP_K,
+            rule_fallback: self.options.rule_fallback,
+            summary: summarize(&results),
+            results,
+        }
+    }
+}
+
+fn summarize(results: &[CaseResult]) -> EvalSummary {
+    let share = |count: usize, total: usize| if total == 0 { 0.0 } else { round(count as f64 / total as f64) };
+    let count = |test: fn(&CaseResult) -> bool| results.iter().filter(|result| test(result)).count();
+    let valued: Vec<bool> = results.iter().filter_map(|result| result.value_correct).collect();
+
+    EvalSummary {
+        cases: results.len(),
+        top1_accuracy: share(count(|result| result.top1), results.len()),
+        top_k_accuracy: share(count(|result| result.top_k), results.len()),
+        setting_accuracy: share(count(|result| result.setting_correct), results.len()),
+        value_accuracy: (!valued.is_empty()).then(|| share(valued.iter().filter(|correct| **correct).count(), valued.len())),
+        parse_failure_rate: share(count(|result| result.parse_failure), results.len()),
+        error_rate: share(count(|result| result.error.is_some()), results.len()),
+        answered_by_rules: count(|result| result.answered_by == intent::RULES_MODEL),
+        retrieval_latency: latency(results.iter().map(|result| result.retrieval_ms).collect()),
+        generate_latency: latency(results.iter().map(|result| result.generate_ms).collect()),
+    }
+}
+
+fn latency(mut samples: Vec<f64>) -> LatencySummary {
+    if samples.is_empty() {
+        return LatencySummary { mean_ms: 0.0, p50_ms: 0.0, p95_ms: 0.0, max_ms: 0.0 };
+    }
+    samples.sort_by(f64::total_cmp);
+    let percentile = |p: f64| samples[((p * samples.len() as f64).ceil() as usize).clamp(1, samples.len()) - 1];
+    LatencySummary {
+        mean_ms: round(samples.iter().sum::<f64>() / samples.len() as f64),
+        p50_ms: round(percentile(0.5)),
+        p95_ms: round(percentile(0.95)),
+        max_ms: round(samples[samples.len() - 1]),
+    }
+}
+
+fn value_matches(actual: &DefaultValue, expected: &Value) -> bool {
+    match (actual, expected) {
+        (DefaultValue::Float(actual), Value::Number(expected)) => {
+            expected.as_f64().is_some_and(|expected| (*actual as f64 - expected).abs() <= VALUE_TOLERANCE)
+        }
+        (DefaultValue::Bool(actual), Value::Bool(expected)) => actual == expected,
+        (DefaultValue::String(actual), Value::String(expected)) => actual.eq_ignore_ascii_case(expected),
+        _ => false,
+    }
+}
+
+fn elapsed_ms(started: Instant) -> f64 {
+    round(started.elapsed().as_secs_f64() * 1000.0)
+}
+
+// Keeps the report readable and stable enough to diff.
+fn round(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
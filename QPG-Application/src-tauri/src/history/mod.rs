@@ -0,0 +1,145 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::models::{ChangeRecord, ChangeSource, DefaultValue};
+
+const HISTORY_LIMIT: usize = 500;
+
+#[derive(Default)]
+pub struct ChangeHistory {
+    applied: Vec<ChangeRecord>,
+    undone: Vec<ChangeRecord>,
+    next_id: u64,
+}
+
+impl ChangeHistory {
+    // Undo, redo and rollback replay existing records rather than adding new ones,
+    // so they never show up as entries of their own.
+    pub fn record(
+        &mut self,
+        setting: &str,
+        command: &str,
+        old_value: Option<DefaultValue>,
+        new_value: DefaultValue,
+        source: ChangeSource,
+    ) -> Option<ChangeRecord> {
+        if matches!(source, ChangeSource::Undo | ChangeSource::Redo | ChangeSource::Rollback) {
+            return None;
+        }
+
+        self.next_id += 1;
+        let record = ChangeRecord {
+            id: self.next_id,
+            setting: setting.to_string(),
+            command: command.to_string(),
+            old_value,
+            new_value,
+            source,
+            timestamp: now_millis(),
+        };
+
+        self.applied.push(record.clone());
+        if self.applied.len() > HISTORY_LIMIT {
+            self.applied.remove(0);
+        }
+        self.undone.clear();
+        Some(record)
+    }
+
+    // A live value that differs from the last one the app set, or from the stored
+    // preference when the app hasn't touched the setting, was changed outside the app.
+    pub fn record_external(
+        &mut self,
+        setting: &str,
+        command: &str,
+        stored: &DefaultValue,
+        live: &DefaultValue,
+    ) -> Option<ChangeRecord> {
+        let known = self
+            .applied
+            .iter()
+            .rev()
+            .find(|record| record.setting == setting)
+            .map_or(stored, |record| &record.new_value)
+            .clone();
+        if crate::settings::same_value(&known, live) {
+            return None;
+        }
+        self.record(setting, command, Some(known), live.clone(), ChangeSource::External)
+    }
+
+    pub fn entries(&self) -> Vec<ChangeRecord> {
+        self.applied.clone()
+    }
+
+    // Startup records have no known old value, so there is nothing to undo them to.
+    pub fn take_undo(&mut self) -> Option<ChangeRecord> {
+        let index = self.applied.iter().rposition(|r| r.old_value.is_some())?;
+        Some(self.applied.remove(index))
+    }
+
+    pub fn take_redo(&mut self) -> Option<ChangeRecord> {
+        self.undone.pop()
+    }
+
+    pub fn discard_last(&mut self, setting: &str) -> Option<ChangeRecord> {
+        let index = self.applied.iter().rposition(|r| r.setting == setting)?;
+        Some(self.applied.remove(index))
+    }
+
+    pub fn push_undone(&mut self, record: ChangeRecord) {
+        self.undone.push(record);
+    }
+
+    pub fn push_applied(&mut self, record: ChangeRecord) {
+        self.applied.push(record);
+    }
+
+    // The value a setting had at `timestamp` is the old value of the first change
+    // made to it after that moment. Settings untouched since then are left out.
+    pub fn values_at(&self, timestamp: u64) -> HashMap<String, DefaultValue> {
+        let mut values = HashMap::new();
+        let mut seen = HashSet::new();
+        for record in self.applied.iter().filter(|r| r.timestamp > timestamp) {
+            if !seen.insert(record.setting.as_str()) {
+                continue;
+            }
+            if let Some(old_value) = &record.old_value {
+                values.insert(record.setting.clone(), old_value.clone());
+            }
+        }
+        values
+    }
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT_SIZE: &str = "gsettings set org.gnome.desktop.interface text-scaling-factor";
+
+    #[test]
+    fn outside_changes_are_recorded_once() {
+        let mut history = ChangeHistory::default();
+        let stored = DefaultValue::Float(1.0);
+        assert!(history.record_external("zoom", TEXT_SIZE, &stored, &DefaultValue::Float(1.0)).is_none());
+
+        let record = history.record_external("zoom", TEXT_SIZE, &stored, &DefaultValue::Float(2.0)).unwrap();
+        assert_eq!(record.source, ChangeSource::External);
+        assert_eq!(record.old_value, Some(DefaultValue::Float(1.0)));
+        assert!(history.record_external("zoom", TEXT_SIZE, &stored, &DefaultValue::Float(2.0)).is_none());
+    }
+
+    #[test]
+    fn values_the_app_set_are_not_outside_changes() {
+        let mut history = ChangeHistory::default();
+        history.record("zoom", TEXT_SIZE, Some(DefaultValue::Float(1.0)), DefaultValue::Float(1.5), ChangeSource::Chat);
+        assert!(history.record_external("zoom", TEXT_SIZE, &DefaultValue::Float(1.0), &DefaultValue::Float(1.5)).is_none());
+    }
+}
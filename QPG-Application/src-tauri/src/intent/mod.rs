@@ -0,0 +1,566 @@
+use std::env;
+use once_cell::sync::Lazy;
+use crate::locale::Language;
+use crate::models::{AppConfig, DefaultValue, Setting, SettingAction, SettingMatch};
+use crate::preferences::command_for_env;
+use crate::retrieval::{rank_lexical, MATCH_THRESHOLD};
+use crate::tools;
+
+// Offered as a model when no LLM can be reached, so simple requests still work.
+pub const RULES_MODEL: &str = "rules";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RulesMode {
+    Fallback,
+    FastPath,
+    Only,
+}
+
+// "fallback" (the default) parses a request only when the LLM fails, "fast_path" tries
+// the parser before every LLM call, and "only" never calls the LLM.
+pub static INTENT_RULES: Lazy<RulesMode> = Lazy::new(|| {
+    match env::var("INTENT_RULES").unwrap_or_default().to_lowercase().as_str() {
+        "fast_path" => RulesMode::FastPath,
+        "only" => RulesMode::Only,
+        _ => RulesMode::Fallback,
+    }
+});
+
+// Relative changes move a setting by this many of its steps.
+const SLIGHT_STEPS: f32 = 1.0;
+const DEFAULT_STEPS: f32 = 2.0;
+const LARGE_STEPS: f32 = 4.0;
+// Settings without a step move by this share of their range.
+const DEFAULT_STEP_SHARE: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Word {
+    Increase,
+    Decrease,
+    Enable,
+    Disable,
+    Toggle,
+    Slight,
+    Large,
+}
+
+struct Vocabulary {
+    words: &'static [(Word, &'static [&'static str])],
+    // Words that start a new request within the prompt ("and", "then").
+    clause_breaks: &'static [&'static str],
+    // Words that make the number after them a relative amount ("increase by 0.5").
+    by: &'static [&'static str],
+    // "Switch to my work profile": the word for a profile and the verbs that switch to one.
+    profile: &'static [&'static str],
+    switch: &'static [&'static str],
+    // Answers to "keep these changes?", and the words that may come with one.
+    keep: &'static [&'static str],
+    revert: &'static [&'static str],
+    filler: &'static [&'static str],
+}
+
+const ENGLISH: Vocabulary = Vocabulary {
+    words: &[
+        (Word::Increase, &["increase", "raise", "bigger", "larger", "more", "higher", "grow", "enlarge", "up", "boost"]),
+        (Word::Decrease, &["decrease", "lower", "reduce", "smaller", "less", "fewer", "shrink", "down"]),
+        (Word::Enable, &["enable", "on", "activate", "start", "show"]),
+        (Word::Disable, &["disable", "off", "deactivate", "stop", "hide"]),
+        (Word::Toggle, &["toggle", "flip"]),
+        (Word::Slight, &["a bit", "a little", "a tad", "slightly"]),
+        (Word::Large, &["a lot", "much", "way", "lots"]),
+    ],
+    clause_breaks: &["and", "then", "also"],
+    by: &["by"],
+    profile: &["profile"],
+    switch: &["switch", "change to", "go to", "use", "activate", "load", "apply"],
+    keep: &["keep", "yes", "confirm", "looks good", "that's good"],
+    revert: &["revert", "undo", "go back", "put it back", "change it back", "no", "cancel"],
+    filler: &["please", "it", "that", "this", "them", "the", "change", "changes", "all", "ok", "okay", "thanks", "thank", "you", "sure"],
+};
+
+const SPANISH: Vocabulary = Vocabulary {
+    words: &[
+        (Word::Increase, &["aumenta", "aumentar", "sube", "subir", "agranda", "agrandar", "más grande", "mayor", "más"]),
+        (Word::Decrease, &["reduce", "reducir", "disminuye", "disminuir", "baja", "bajar", "más pequeño", "menor", "menos"]),
+        (Word::Enable, &["activa", "activar", "enciende", "encender", "habilita", "habilitar"]),
+        (Word::Disable, &["desactiva", "desactivar", "apaga", "apagar", "deshabilita", "deshabilitar", "quita", "quitar"]),
+        (Word::Toggle, &["alterna", "alternar"]),
+        (Word::Slight, &["un poco", "ligeramente"]),
+        (Word::Large, &["mucho", "bastante"]),
+    ],
+    clause_breaks: &["y", "luego", "también"],
+    by: &["en"],
+    profile: &["perfil"],
+    switch: &["cambia", "cambiar", "usa", "usar", "activa", "activar", "carga", "cargar", "aplica", "aplicar", "pon", "poner"],
+    keep: &["conserva", "conservar", "mantén", "mantener", "sí", "confirma", "confirmar"],
+    revert: &["revierte", "revertir", "deshaz", "deshacer", "vuelve", "volver", "no", "cancela", "cancelar"],
+    filler: &["por", "favor", "eso", "esto", "lo", "los", "el", "cambio", "cambios", "todo", "todos", "vale", "gracias", "ok"],
+};
+
+const GERMAN: Vocabulary = Vocabulary {
+    words: &[
+        (Word::Increase, &["erhöhe", "erhöhen", "vergrößere", "vergrößern", "größer", "höher", "mehr"]),
+        (Word::Decrease, &["verringere", "verringern", "reduziere", "reduzieren", "verkleinere", "verkleinern", "kleiner", "niedriger", "weniger"]),
+        (Word::Enable, &["aktiviere", "aktivieren", "einschalten", "anschalten", "ein", "an"]),
+        (Word::Disable, &["deaktiviere", "deaktivieren", "ausschalten", "abschalten", "aus"]),
+        (Word::Toggle, &["umschalten"]),
+        (Word::Slight, &["ein bisschen", "ein wenig", "etwas", "leicht"]),
+        (Word::Large, &["viel", "deutlich"]),
+    ],
+    clause_breaks: &["und", "dann", "auch"],
+    by: &["um"],
+    profile: &["profil"],
+    switch: &["wechsle", "wechseln", "nutze", "benutze", "verwende", "verwenden", "aktiviere", "aktivieren", "lade", "laden"],
+    keep: &["behalten", "behalte", "ja", "bestätigen", "bestätige"],
+    revert: &["zurück", "rückgängig", "zurücksetzen", "nein", "abbrechen"],
+    filler: &["bitte", "es", "das", "die", "änderung", "änderungen", "alle", "alles", "mach", "danke", "ok", "okay"],
+};
+
+const FRENCH: Vocabulary = Vocabulary {
+    words: &[
+        (Word::Increase, &["augmente", "augmenter", "agrandis", "agrandir", "plus grand", "plus grande", "plus"]),
+        (Word::Decrease, &["diminue", "diminuer", "réduis", "réduire", "plus petit", "plus petite", "moins"]),
+        (Word::Enable, &["active", "activer", "allume", "allumer"]),
+        (Word::Disable, &["désactive", "désactiver", "éteins", "éteindre", "coupe", "couper"]),
+        (Word::Toggle, &["bascule", "basculer"]),
+        (Word::Slight, &["un peu", "légèrement"]),
+        (Word::Large, &["beaucoup", "nettement"]),
+    ],
+    clause_breaks: &["et", "puis", "aussi"],
+    by: &["de"],
+    profile: &["profil"],
+    switch: &["passe", "passer", "utilise", "utiliser", "active", "activer", "charge", "charger", "applique", "appliquer"],
+    keep: &["garde", "garder", "conserve", "conserver", "oui", "confirme", "confirmer"],
+    revert: &["annule", "annuler", "reviens", "revenir", "non"],
+    filler: &["s'il", "vous", "te", "plaît", "ça", "cela", "le", "la", "les", "changement", "changements", "tout", "tous", "merci", "ok", "d'accord"],
+};
+
+fn vocabulary(language: Language) -> &'static Vocabulary {
+    match language {
+        Language::English => &ENGLISH,
+        Language::Spanish => &SPANISH,
+        Language::German => &GERMAN,
+        Language::French => &FRENCH,
+    }
+}
+
+// Longer messages are requests of their own, not answers to the confirmation prompt.
+const MAX_CONFIRMATION_WORDS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirmation {
+    Keep,
+    Revert,
+}
+
+pub struct Intent {
+    pub actions: Vec<SettingAction>,
+    pub matches: Vec<SettingMatch>,
+}
+
+struct Number {
+    value: f32,
+    relative: bool,
+}
+
+// Reads simple requests like "set zoom to 1.5" or "turn on the screen reader and
+// make the cursor a bit bigger" from the catalog alone. Every part of the prompt has
+// to resolve to a setting and a value, otherwise nothing is returned and the request
+// is left to the model. `setting` is the one the user picked from a clarifying question.
+pub fn parse(
+    prompt: &str,
+    config: &AppConfig,
+    filtered_json: &str,
+    env: &str,
+    language: Language,
+    setting: Option<&str>,
+) -> Option<Intent> {
+    let vocabulary = vocabulary(language);
+    let clauses = split_clauses(prompt, vocabulary);
+    if clauses.is_empty() || (setting.is_some() && clauses.len() > 1) {
+        return None;
+    }
+
+    let mut intent = Intent { actions: Vec::new(), matches: Vec::new() };
+    let mut previous_words: Vec<Word> = Vec::new();
+    for tokens in clauses {
+        let words: Vec<String> = tokens.iter().map(|token| normalize(token)).collect();
+        let (setting_match, rest) = match setting {
+            Some(key) => (SettingMatch { setting: key.to_string(), score: 1.0 }, words.clone()),
+            None => find_setting(&tokens, &words, config, filtered_json, env, language)?,
+        };
+        let current = config.get(&setting_match.setting)?;
+
+        // A key like "on screen keyboard" holds a verb of its own, so when nothing
+        // is left after taking it out the whole clause is read.
+        let mut found = detect_words(&rest, vocabulary);
+        if found.iter().all(|word| matches!(word, Word::Slight | Word::Large)) {
+            found.extend(detect_words(&words, vocabulary));
+        }
+        // "turn on the screen reader and the magnifier" applies the verb to both.
+        if !found.iter().any(|word| !matches!(word, Word::Slight | Word::Large)) {
+            found.extend(previous_words.iter().copied());
+        }
+
+        let number = find_number(&tokens, vocabulary).ok()?;
+        let value = target_value(current, &found, number, &words)?;
+        let base_command = command_for_env(current, env);
+        if base_command.is_empty() {
+            return None;
+        }
+        let action = tools::action_from_command(config, env, &format!("{} {}", base_command, value)).ok()?;
+
+        previous_words = found;
+        intent.actions.push(action);
+        intent.matches.push(setting_match);
+    }
+    Some(intent)
+}
+
+// Reads a short answer like "keep it" or "undo that" to changes waiting for
+// confirmation. The whole message has to be the answer, so "no animations please"
+// stays a request of its own. Answers that say both, or neither, are left alone.
+pub fn parse_confirmation(prompt: &str, language: Language) -> Option<Confirmation> {
+    let vocabulary = vocabulary(language);
+    let mut words: Vec<String> = prompt.split_whitespace().map(normalize).filter(|word| !word.is_empty()).collect();
+    if words.is_empty() || words.len() > MAX_CONFIRMATION_WORDS {
+        return None;
+    }
+    let mut says = |phrases: &[&str]| {
+        let mut found = false;
+        for phrase in phrases {
+            while take_phrase(&mut words, phrase) {
+                found = true;
+            }
+        }
+        found
+    };
+    let answer = (says(vocabulary.keep), says(vocabulary.revert));
+    if words.iter().any(|word| !word.is_empty() && !vocabulary.filler.contains(&word.as_str())) {
+        return None;
+    }
+    match answer {
+        (true, false) => Some(Confirmation::Keep),
+        (false, true) => Some(Confirmation::Revert),
+        _ => None,
+    }
+}
+
+// A cheap check before the profiles are loaded to look for a name.
+pub fn mentions_profile(prompt: &str, language: Language) -> bool {
+    let vocabulary = vocabulary(language);
+    prompt
+        .split_whitespace()
+        .map(normalize)
+        .any(|word| vocabulary.profile.contains(&word.as_str()))
+}
+
+// Reads "switch to my work profile" as a request to activate the profile named in
+// it. The longest name found wins, so "work" doesn't shadow "work late".
+pub fn parse_profile(prompt: &str, names: &[String], language: Language) -> Option<String> {
+    let vocabulary = vocabulary(language);
+    let words: Vec<String> = prompt.split_whitespace().map(normalize).collect();
+    if !words.iter().any(|word| vocabulary.profile.contains(&word.as_str())) {
+        return None;
+    }
+    if !vocabulary.switch.iter().any(|verb| take_phrase(&mut words.clone(), verb)) {
+        return None;
+    }
+
+    let mut names: Vec<&String> = names.iter().collect();
+    names.sort_by_key(|name| std::cmp::Reverse(name.split_whitespace().count()));
+    names
+        .into_iter()
+        .find(|name| take_phrase(&mut words.clone(), &name.to_lowercase()))
+        .cloned()
+}
+
+// Splits on clause words and on commas or semicolons that end a word, but not on
+// decimal commas ("1,5").
+fn split_clauses(prompt: &str, vocabulary: &Vocabulary) -> Vec<Vec<String>> {
+    let mut clauses: Vec<Vec<String>> = vec![Vec::new()];
+    for token in prompt.to_lowercase().split_whitespace() {
+        if vocabulary.clause_breaks.contains(&normalize(token).as_str()) {
+            clauses.push(Vec::new());
+            continue;
+        }
+        let ends_clause = token.ends_with(',') || token.ends_with(';');
+        if let Some(clause) = clauses.last_mut() {
+            clause.push(token.trim_end_matches([',', ';']).to_string());
+        }
+        if ends_clause {
+            clauses.push(Vec::new());
+        }
+    }
+    clauses.retain(|clause| clause.iter().any(|token| !normalize(token).is_empty()));
+    clauses
+}
+
+fn normalize(token: &str) -> String {
+    token
+        .to_lowercase()
+        .replace('_', " ")
+        .trim_matches(|c: char| !c.is_alphanumeric() && c != '\'')
+        .to_string()
+}
+
+// The longest setting name or synonym in the clause wins; without one, the clause is
+// ranked like a chat message and has to clear the usual match threshold. A matched
+// setting name is removed from the words so its own words aren't read as verbs, while
+// synonyms like "bigger text" are left in since they often carry the verb.
+fn find_setting(
+    tokens: &[String],
+    words: &[String],
+    config: &AppConfig,
+    filtered_json: &str,
+    env: &str,
+    language: Language,
+) -> Option<(SettingMatch, Vec<String>)> {
+    let mut phrases: Vec<(String, &String, bool)> = Vec::new();
+    for (key, setting) in config.iter().filter(|(_, setting)| !command_for_env(setting, env).is_empty()) {
+        phrases.push((key.replace('_', " "), key, true));
+        let translated = setting.translations.get(language.code()).map(|text| text.synonyms.as_slice());
+        for synonym in setting.synonyms.iter().chain(translated.unwrap_or_default()) {
+            phrases.push((synonym.to_lowercase(), key, false));
+        }
+    }
+    phrases.sort_by(|a, b| {
+        let length = |phrase: &str| (phrase.split_whitespace().count(), phrase.len());
+        length(&b.0).cmp(&length(&a.0)).then_with(|| a.1.cmp(b.1))
+    });
+
+    for (phrase, key, is_name) in &phrases {
+        let mut rest = words.to_vec();
+        if take_phrase(&mut rest, phrase) {
+            let rest = if *is_name { rest } else { words.to_vec() };
+            return Some((SettingMatch { setting: (*key).clone(), score: 1.0 }, rest));
+        }
+    }
+
+    let best = rank_lexical(&tokens.join(" "), filtered_json, language, 1).into_iter().next()?;
+    (best.score >= *MATCH_THRESHOLD).then(|| (best, words.to_vec()))
+}
+
+// Finds `phrase` as consecutive words and blanks them out, so words inside a longer
+// phrase ("plus petit") aren't matched again on their own ("plus").
+fn take_phrase(words: &mut [String], phrase: &str) -> bool {
+    let parts: Vec<String> = phrase.split_whitespace().map(normalize).collect();
+    if parts.is_empty() || parts.len() > words.len() {
+        return false;
+    }
+    for start in 0..=words.len() - parts.len() {
+        let window = &mut words[start..start + parts.len()];
+        if window.iter().zip(&parts).all(|(word, part)| word == part) {
+            window.iter_mut().for_each(String::clear);
+            return true;
+        }
+    }
+    false
+}
+
+fn detect_words(words: &[String], vocabulary: &Vocabulary) -> Vec<Word> {
+    let mut phrases: Vec<(Word, &str)> = vocabulary
+        .words
+        .iter()
+        .flat_map(|(word, phrases)| phrases.iter().map(move |phrase| (*word, *phrase)))
+        .collect();
+    phrases.sort_by_key(|(_, phrase)| std::cmp::Reverse(phrase.split_whitespace().count()));
+
+    let mut words = words.to_vec();
+    let mut found = Vec::new();
+    for (word, phrase) in phrases {
+        while take_phrase(&mut words, phrase) {
+            if !found.contains(&word) {
+                found.push(word);
+            }
+        }
+    }
+    found
+}
+
+// More than one number, or a percentage, is left to the model.
+fn find_number(tokens: &[String], vocabulary: &Vocabulary) -> Result<Option<Number>, ()> {
+    let mut number = None;
+    for (index, token) in tokens.iter().enumerate() {
+        let digits = token.trim_matches(|c: char| !c.is_ascii_digit());
+        if digits.is_empty() {
+            continue;
+        }
+        if token.contains('%') || number.is_some() {
+            return Err(());
+        }
+        let value: f32 = digits.replace(',', ".").parse().map_err(|_| ())?;
+        let relative = index > 0 && vocabulary.by.contains(&normalize(&tokens[index - 1]).as_str());
+        number = Some(Number { value, relative });
+    }
+    Ok(number)
+}
+
+fn target_value(setting: &Setting, found: &[Word], number: Option<Number>, words: &[String]) -> Option<DefaultValue> {
+    let has = |word: Word| found.contains(&word);
+    match &setting.current {
+        DefaultValue::Float(current) => {
+            let up = has(Word::Increase);
+            let down = has(Word::Decrease);
+            let steps = if has(Word::Slight) {
+                SLIGHT_STEPS
+            } else if has(Word::Large) {
+                LARGE_STEPS
+            } else {
+                DEFAULT_STEPS
+            };
+            let value = match (number, up, down) {
+                (Some(n), true, false) if n.relative => current + n.value,
+                (Some(n), false, true) if n.relative => current - n.value,
+                // "en" and "de" also mean "to" without a verb saying which way.
+                (Some(n), false, false) => n.value,
+                (Some(n), _, _) if n.relative => return None,
+                (Some(n), _, _) => n.value,
+                (None, true, false) => current + step(setting) * steps,
+                (None, false, true) => current - step(setting) * steps,
+                _ => return None,
+            };
+            Some(DefaultValue::Float(fit_to_setting(setting, value)))
+        }
+        DefaultValue::Bool(current) => {
+            let on = has(Word::Enable) || has(Word::Increase);
+            let off = has(Word::Disable) || has(Word::Decrease);
+            match (on, off) {
+                (true, false) => Some(DefaultValue::Bool(true)),
+                (false, true) => Some(DefaultValue::Bool(false)),
+                (false, false) if has(Word::Toggle) => Some(DefaultValue::Bool(!current)),
+                _ => None,
+            }
+        }
+        // Free-form strings such as font names are left to the model.
+        DefaultValue::String(_) => {
+            let options = setting.options.as_ref()?;
+            let mut named = options.iter().filter(|option| {
+                let mut rest = words.to_vec();
+                take_phrase(&mut rest, &option.replace(['-', '_'], " "))
+            });
+            let option = named.next()?;
+            named.next().is_none().then(|| DefaultValue::String(option.clone()))
+        }
+    }
+}
+
+fn step(setting: &Setting) -> f32 {
+    if let Some(step) = setting.step.filter(|step| *step > 0.0) {
+        return step;
+    }
+    match (setting.lower_bound, setting.upper_bound) {
+        (Some(lower), Some(upper)) if upper > lower => (upper - lower) * DEFAULT_STEP_SHARE,
+        _ => 1.0,
+    }
+}
+
+// Clamped to the bounds, and kept whole for settings that only take whole numbers.
+fn fit_to_setting(setting: &Setting, value: f32) -> f32 {
+    let mut value = value;
+    if let Some(lower) = setting.lower_bound {
+        value = value.max(lower);
+    }
+    if let Some(upper) = setting.upper_bound {
+        value = value.min(upper);
+    }
+    let whole = |v: f32| v.fract() == 0.0;
+    let current_whole = matches!(setting.current, DefaultValue::Float(current) if whole(current));
+    if current_whole && setting.step.is_some_and(whole) {
+        return value.round();
+    }
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(prompt: &str, language: Language) -> Vec<(String, DefaultValue)> {
+        let catalog = include_str!("../json_example.json");
+        let config: AppConfig = serde_json::from_str(catalog).unwrap();
+        let filtered_json = crate::preferences::filter_json_by_env(catalog, "gnome").unwrap();
+        parse(prompt, &config, &filtered_json, "gnome", language, None)
+            .map(|intent| intent.actions.into_iter().map(|action| (action.setting, action.value)).collect())
+            .unwrap_or_default()
+    }
+
+    fn float(setting: &str, value: f32) -> Vec<(String, DefaultValue)> {
+        vec![(setting.to_string(), DefaultValue::Float(value))]
+    }
+
+    fn boolean(setting: &str, value: bool) -> Vec<(String, DefaultValue)> {
+        vec![(setting.to_string(), DefaultValue::Bool(value))]
+    }
+
+    #[test]
+    fn numbers_set_the_value() {
+        assert_eq!(parsed("set the zoom to 1.75", Language::English), float("zoom", 1.75));
+        assert_eq!(parsed("increase the zoom by 0.5", Language::English), float("zoom", 1.5));
+        assert_eq!(parsed("pon el zoom en 1,5", Language::Spanish), float("zoom", 1.5));
+        assert_eq!(parsed("Zoom um 0,25 erhöhen", Language::German), float("zoom", 1.25));
+    }
+
+    #[test]
+    fn relative_changes_move_by_the_setting_step() {
+        // zoom steps by 0.25, cursor_size by 8 and the magnifier by 0.5.
+        assert_eq!(parsed("increase the zoom", Language::English), float("zoom", 1.5));
+        assert_eq!(parsed("make the cursor size smaller a bit", Language::English), float("cursor_size", 16.0));
+        assert_eq!(parsed("increase the cursor size", Language::English), float("cursor_size", 40.0));
+        assert_eq!(parsed("increase the magnifier a lot", Language::English), float("magnifier", 3.0));
+        assert_eq!(parsed("decrease the zoom", Language::English), float("zoom", 0.5));
+    }
+
+    #[test]
+    fn switches_turn_on_off_and_toggle() {
+        assert_eq!(parsed("turn on the screen reader", Language::English), boolean("screen_reader", true));
+        assert_eq!(parsed("enable locate pointer", Language::English), boolean("locate_pointer", true));
+        assert_eq!(parsed("disable enable animation", Language::English), boolean("enable_animation", false));
+        assert_eq!(parsed("toggle the screen reader", Language::English), boolean("screen_reader", true));
+        assert_eq!(parsed("toggle enable animation", Language::English), boolean("enable_animation", false));
+    }
+
+    #[test]
+    fn vague_or_mixed_requests_are_left_to_the_model() {
+        assert!(parsed("zoom", Language::English).is_empty());
+        assert!(parsed("set the zoom to 150%", Language::English).is_empty());
+        assert!(parsed("set the zoom to 1.5 or 2", Language::English).is_empty());
+    }
+
+    fn names() -> Vec<String> {
+        ["Work", "Work late", "Evening"].iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn profile_switches_pick_the_longest_name() {
+        assert_eq!(parse_profile("switch to my work profile", &names(), Language::English).as_deref(), Some("Work"));
+        assert_eq!(parse_profile("use the work late profile", &names(), Language::English).as_deref(), Some("Work late"));
+        assert_eq!(parse_profile("cambia al perfil evening", &names(), Language::Spanish).as_deref(), Some("Evening"));
+        assert_eq!(parse_profile("Wechsle zum Profil Work", &names(), Language::German).as_deref(), Some("Work"));
+    }
+
+    #[test]
+    fn short_answers_settle_pending_changes() {
+        assert_eq!(parse_confirmation("keep it", Language::English), Some(Confirmation::Keep));
+        assert_eq!(parse_confirmation("Yes!", Language::English), Some(Confirmation::Keep));
+        assert_eq!(parse_confirmation("put it back please", Language::English), Some(Confirmation::Revert));
+        assert_eq!(parse_confirmation("deshaz eso", Language::Spanish), Some(Confirmation::Revert));
+        assert_eq!(parse_confirmation("behalten", Language::German), Some(Confirmation::Keep));
+        assert_eq!(parse_confirmation("non, annule", Language::French), Some(Confirmation::Revert));
+        assert_eq!(parse_confirmation("ok, keep them please", Language::English), Some(Confirmation::Keep));
+        assert_eq!(parse_confirmation("annule tout s'il te plaît", Language::French), Some(Confirmation::Revert));
+        assert_eq!(parse_confirmation("yes, no", Language::English), None);
+        assert_eq!(parse_confirmation("no animations please", Language::English), None);
+        assert_eq!(parse_confirmation("yes make it bigger", Language::English), None);
+        assert_eq!(parse_confirmation("nein, mehr Kontrast", Language::German), None);
+        assert_eq!(parse_confirmation("keep the text bigger but make the cursor smaller", Language::English), None);
+    }
+
+    #[test]
+    fn profile_switches_need_a_verb_and_a_known_name() {
+        assert_eq!(parse_profile("what is in my work profile", &names(), Language::English), None);
+        assert_eq!(parse_profile("switch to my gaming profile", &names(), Language::English), None);
+        assert_eq!(parse_profile("switch to work", &names(), Language::English), None);
+        assert!(!mentions_profile("make the text bigger", Language::English));
+    }
+}
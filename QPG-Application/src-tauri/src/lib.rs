@@ -6,11 +6,36 @@ mod preferences;
 mod state;
 mod encryption;
 mod models;
+mod confirmation;
+mod history;
+mod platform;
+mod presets;
+mod profiles;
+pub mod locale;
+pub mod retrieval;
+mod settings;
+mod tools;
+mod chats;
+mod context;
+pub mod llm;
+pub mod intent;
+mod model_manager;
+mod prompts;
+mod preview;
+pub mod eval;
 
 pub use commands::{
-    execute_command, execute_startup_app_command, fetch_preferences,
-    generate, get_username, init_startup_commands,
-    init_startup_apps, list_models, check_encryption_client, fetch_full_json
+    execute_command, execute_actions, execute_startup_app_command, fetch_preferences,
+    generate, generate_stream, cancel_generation, get_username, init_startup_commands,
+    init_startup_apps, list_models, check_encryption_client, fetch_full_json,
+    get_platform, list_profiles, get_active_profile, create_profile,
+    rename_profile, delete_profile, diff_profile, activate_profile,
+    list_presets, reload_presets, preview_preset, apply_preset,
+    get_change_history, undo_change, redo_change, restore_to,
+    list_pending_changes, confirm_change, revert_change, read_live_settings,
+    list_chats, open_chat, rename_chat, delete_chat, export_chat,
+    pull_model, cancel_model_pull, delete_model, recommend_model, warm_up_model,
+    get_model_status
 };
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -24,6 +49,7 @@ pub fn run() {
             let app_handle = app.app_handle().clone();
             
             app.manage(state::GenerateState::default());
+            prompts::load();
             
             let encryption_client = tauri::async_runtime::block_on(async {
                 match encryption::EncryptionClient::new(&preferences::SERVER_URL.to_string()).await {
@@ -58,6 +84,12 @@ pub fn run() {
                 }
             });
 
+            let preload_handle = app.app_handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let llm = preload_handle.state::<state::LlmInstance>().0.clone();
+                model_manager::preload_on_startup(&preload_handle, llm).await;
+            });
+
 	    let listener_handle = app.app_handle().clone();
             listener_handle.listen("frontend-loaded", {
                 let captured_handle = listener_handle.clone();
@@ -83,25 +115,54 @@ pub fn run() {
         })
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_opener::init())
-        .manage(state::OllamaInstance(tauri::async_runtime::Mutex::new(
-            ollama_rs::Ollama::new_with_history_from_url(
-                url::Url::parse(&preferences::OLLAMA_BASE_URL.to_string()).unwrap(),
-                50,
-            )
-        )))
-        .manage(state::ChatIDs(tauri::async_runtime::Mutex::new(std::collections::HashMap::new())))
+        .manage(state::LlmInstance(llm::from_config()))
+        .manage(state::ChatHistories::default())
         .manage(state::GenerateState::default())
         .invoke_handler(tauri::generate_handler![
             list_models,
+            pull_model,
+            cancel_model_pull,
+            delete_model,
+            recommend_model,
+            warm_up_model,
+            get_model_status,
             init_startup_commands,
 	    init_startup_apps,
             generate,
+            generate_stream,
+            cancel_generation,
             fetch_preferences,
             execute_command,
+            execute_actions,
 	    execute_startup_app_command,
             get_username,
             check_encryption_client,
-            fetch_full_json
+            fetch_full_json,
+            get_platform,
+            list_profiles,
+            get_active_profile,
+            create_profile,
+            rename_profile,
+            delete_profile,
+            diff_profile,
+            activate_profile,
+            list_presets,
+            reload_presets,
+            preview_preset,
+            apply_preset,
+            get_change_history,
+            undo_change,
+            redo_change,
+            restore_to,
+            list_pending_changes,
+            confirm_change,
+            revert_change,
+            read_live_settings,
+            list_chats,
+            open_chat,
+            rename_chat,
+            delete_chat,
+            export_chat
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
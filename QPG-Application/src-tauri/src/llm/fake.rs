@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use async_trait::async_trait;
+use serde::Deserialize;
+use super::{ChatMessage, LlmBackend, LlmError, LlmReply, LlmRequest, OnContent, OnPullProgress, PullProgress, ToolCall};
+
+const EMBEDDING_DIMENSIONS: usize = 64;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FakeReply {
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+impl FakeReply {
+    pub fn text(content: &str) -> Self {
+        Self { content: content.to_string(), tool_calls: Vec::new() }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct FakeScript {
+    #[serde(default)]
+    models: Vec<String>,
+    #[serde(default)]
+    supports_tools: bool,
+    #[serde(default)]
+    replies: Vec<FakeReply>,
+}
+
+// Plays back scripted replies in order and records every request, so the generate
+// pipeline can be run without a model. Requests with tools are refused unless the
+// script says otherwise, which sends the pipeline down the JSON reply path.
+pub struct FakeBackend {
+    models: Mutex<Vec<String>>,
+    supports_tools: bool,
+    replies: Mutex<VecDeque<FakeReply>>,
+    requests: Mutex<Vec<LlmRequest>>,
+}
+
+impl Default for FakeBackend {
+    fn default() -> Self {
+        Self::new(vec!["fake".to_string()], false)
+    }
+}
+
+impl FakeBackend {
+    pub fn new(models: Vec<String>, supports_tools: bool) -> Self {
+        Self {
+            models: Mutex::new(models),
+            supports_tools,
+            replies: Mutex::new(VecDeque::new()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    // {"models": [...], "supports_tools": false, "replies": [{"content": "...", "tool_calls": [...]}]}
+    pub fn from_script_file(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let script: FakeScript = serde_json::from_str(&contents).map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+        let models = if script.models.is_empty() { vec!["fake".to_string()] } else { script.models };
+
+        let backend = Self::new(models, script.supports_tools);
+        for reply in script.replies {
+            backend.push_reply(reply);
+        }
+        Ok(backend)
+    }
+
+    pub fn push_reply(&self, reply: FakeReply) {
+        self.replies.lock().unwrap().push_back(reply);
+    }
+
+    pub fn clear_replies(&self) {
+        self.replies.lock().unwrap().clear();
+    }
+
+    pub fn remaining_replies(&self) -> usize {
+        self.replies.lock().unwrap().len()
+    }
+
+    pub fn requests(&self) -> Vec<LlmRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl LlmBackend for FakeBackend {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    async fn chat_stream(
+        &self,
+        request: &LlmRequest,
+        on_content: &mut OnContent<'_>,
+    ) -> Result<LlmReply, LlmError> {
+        self.requests.lock().unwrap().push(request.clone());
+        if !request.tools.is_empty() && !self.supports_tools {
+            return Err(LlmError::ToolsUnsupported);
+        }
+        let scripted = self
+            .replies
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| LlmError::Failed("The fake backend has no scripted reply left".to_string()))?;
+
+        // Streamed a word at a time, like a model would.
+        let mut content = String::new();
+        for word in scripted.content.split_inclusive(' ') {
+            content.push_str(word);
+            on_content(&content);
+        }
+
+        Ok(LlmReply {
+            model: request.model.clone(),
+            created_at: String::new(),
+            message: ChatMessage::assistant(content),
+            done: true,
+            tool_calls: scripted.tool_calls,
+        })
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, String> {
+        Ok(self.models.lock().unwrap().clone())
+    }
+
+    async fn pull_model(&self, model: &str, on_progress: &mut OnPullProgress<'_>) -> Result<(), String> {
+        on_progress(PullProgress { status: "pulling manifest".to_string(), ..Default::default() });
+        on_progress(PullProgress { status: "success".to_string(), ..Default::default() });
+        let mut models = self.models.lock().unwrap();
+        if !models.iter().any(|installed| installed == model) {
+            models.push(model.to_string());
+        }
+        Ok(())
+    }
+
+    async fn delete_model(&self, model: &str) -> Result<(), String> {
+        let mut models = self.models.lock().unwrap();
+        let count = models.len();
+        models.retain(|installed| installed != model);
+        if models.len() == count {
+            return Err(format!("Model {} is not installed", model));
+        }
+        Ok(())
+    }
+
+    // Hashed bag of words: texts that share words point the same way, which is
+    // enough to exercise embedding ranking deterministically.
+    async fn embed(&self, _model: &str, input: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+        Ok(input
+            .iter()
+            .map(|text| {
+                let mut vector = vec![0.0; EMBEDDING_DIMENSIONS];
+                for word in text.to_lowercase().split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+                    let mut hasher = DefaultHasher::new();
+                    word.hash(&mut hasher);
+                    vector[hasher.finish() as usize % EMBEDDING_DIMENSIONS] += 1.0;
+                }
+                vector
+            })
+            .collect())
+    }
+}
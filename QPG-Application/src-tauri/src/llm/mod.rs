@@ -0,0 +1,305 @@
+pub mod fake;
+pub mod ollama;
+pub mod openai;
+
+use std::env;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::preferences::OLLAMA_BASE_URL;
+
+// "ollama" (the default), "openai" for llama.cpp's server and other OpenAI-compatible
+// endpoints, or "fake" to replay LLM_FAKE_SCRIPT.
+pub static LLM_BACKEND: Lazy<String> = Lazy::new(|| {
+    env::var("LLM_BACKEND").unwrap_or_else(|_| "ollama".to_string()).to_lowercase()
+});
+pub static LLM_BASE_URL: Lazy<String> = Lazy::new(|| {
+    env::var("LLM_BASE_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| "http://localhost:8080/v1".to_string())
+});
+pub static LLM_API_KEY: Lazy<Option<String>> = Lazy::new(|| {
+    env::var("LLM_API_KEY").ok().filter(|key| !key.is_empty())
+});
+// Pulled in the background when the backend has no models yet.
+pub static DEFAULT_MODEL: Lazy<String> = Lazy::new(|| {
+    env::var("DEFAULT_MODEL")
+        .ok()
+        .filter(|model| !model.is_empty())
+        .unwrap_or_else(|| "granite3-dense:8b".to_string())
+});
+// How long Ollama keeps a model in memory after a request ("30m", "2h", or "-1" to
+// never unload it).
+pub static MODEL_KEEP_ALIVE: Lazy<String> = Lazy::new(|| {
+    env::var("MODEL_KEEP_ALIVE")
+        .ok()
+        .filter(|keep_alive| !keep_alive.is_empty())
+        .unwrap_or_else(|| "30m".to_string())
+});
+// MODEL_KEEP_ALIVE as a duration, or None when the model is never unloaded.
+pub static MODEL_KEEP_ALIVE_DURATION: Lazy<Option<Duration>> = Lazy::new(|| parse_keep_alive(&MODEL_KEEP_ALIVE));
+pub static LLM_FAKE_SCRIPT: Lazy<Option<String>> = Lazy::new(|| {
+    env::var("LLM_FAKE_SCRIPT").ok().filter(|path| !path.is_empty())
+});
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageRole {
+    User,
+    Assistant,
+    System,
+    // The result of a tool call, following the assistant message that made it.
+    Tool,
+}
+
+// Serialized the same way by Ollama and OpenAI-compatible servers, and by chats saved
+// before the backend was pluggable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: MessageRole,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+impl ChatMessage {
+    pub fn new(role: MessageRole, content: String) -> Self {
+        Self { role, content, tool_calls: Vec::new() }
+    }
+
+    pub fn user(content: String) -> Self {
+        Self::new(MessageRole::User, content)
+    }
+
+    pub fn assistant(content: String) -> Self {
+        Self::new(MessageRole::Assistant, content)
+    }
+
+    pub fn system(content: String) -> Self {
+        Self::new(MessageRole::System, content)
+    }
+
+    pub fn tool(content: String) -> Self {
+        Self::new(MessageRole::Tool, content)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub function: ToolCallFunction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallFunction {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct LlmRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    // Context window to ask for, where the backend lets the client choose.
+    pub window: usize,
+    // JSON schema the reply has to follow.
+    pub format: Option<Value>,
+    pub tools: Vec<Value>,
+}
+
+impl LlmRequest {
+    pub fn new(model: &str, messages: Vec<ChatMessage>, window: usize) -> Self {
+        Self {
+            model: model.to_string(),
+            messages,
+            window,
+            format: None,
+            tools: Vec::new(),
+        }
+    }
+
+    pub fn format(mut self, schema: Value) -> Self {
+        self.format = Some(schema);
+        self
+    }
+
+    pub fn tools(mut self, tools: Vec<Value>) -> Self {
+        self.tools = tools;
+        self
+    }
+}
+
+// Keeps the shape of Ollama's chat response, which the frontend reads.
+#[derive(Debug, Clone, Serialize)]
+pub struct LlmReply {
+    pub model: String,
+    pub created_at: String,
+    pub message: ChatMessage,
+    pub done: bool,
+    #[serde(skip)]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+#[derive(Debug, Clone)]
+pub enum LlmError {
+    // The model can't take the tools it was given; ask again in JSON mode.
+    ToolsUnsupported,
+    Failed(String),
+}
+
+impl fmt::Display for LlmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LlmError::ToolsUnsupported => write!(f, "the model does not support tools"),
+            LlmError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<String> for LlmError {
+    fn from(e: String) -> Self {
+        LlmError::Failed(e)
+    }
+}
+
+impl From<LlmError> for String {
+    fn from(e: LlmError) -> Self {
+        e.to_string()
+    }
+}
+
+// Receives the reply text accumulated so far as it streams in.
+pub type OnContent<'a> = dyn FnMut(&str) + Send + 'a;
+
+// One status line from a model download. Sizes are in bytes and only known while a
+// layer is downloading.
+#[derive(Debug, Clone, Default)]
+pub struct PullProgress {
+    pub status: String,
+    pub completed: Option<u64>,
+    pub total: Option<u64>,
+}
+
+pub type OnPullProgress<'a> = dyn FnMut(PullProgress) + Send + 'a;
+
+#[async_trait]
+pub trait LlmBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn chat_stream(
+        &self,
+        request: &LlmRequest,
+        on_content: &mut OnContent<'_>,
+    ) -> Result<LlmReply, LlmError>;
+
+    async fn chat(&self, request: &LlmRequest) -> Result<LlmReply, LlmError> {
+        self.chat_stream(request, &mut |_| {}).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, String>;
+
+    async fn embed(&self, model: &str, input: Vec<String>) -> Result<Vec<Vec<f32>>, String>;
+
+    // The context length the model was trained with, when the backend reports it.
+    async fn context_length(&self, _model: &str) -> Result<Option<usize>, String> {
+        Ok(None)
+    }
+
+    // Loads the model into memory ahead of the first request. Servers that load their
+    // model when they start have nothing to do.
+    async fn load_model(&self, _model: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn pull_model(&self, model: &str, _on_progress: &mut OnPullProgress<'_>) -> Result<(), String> {
+        Err(format!("The {} backend can't download {}", self.name(), model))
+    }
+
+    async fn delete_model(&self, model: &str) -> Result<(), String> {
+        Err(format!("The {} backend can't delete {}", self.name(), model))
+    }
+}
+
+pub fn from_config() -> Arc<dyn LlmBackend> {
+    match LLM_BACKEND.as_str() {
+        "openai" => {
+            println!("[llm] Using the OpenAI-compatible endpoint at {}", *LLM_BASE_URL);
+            Arc::new(openai::OpenAiBackend::new(&LLM_BASE_URL, LLM_API_KEY.clone()))
+        }
+        "fake" => {
+            let backend = match LLM_FAKE_SCRIPT.as_deref() {
+                Some(path) => fake::FakeBackend::from_script_file(path).unwrap_or_else(|e| {
+                    println!("[llm] {}; the fake backend has no replies", e);
+                    fake::FakeBackend::default()
+                }),
+                None => fake::FakeBackend::default(),
+            };
+            println!("[llm] Using the scripted fake backend");
+            Arc::new(backend)
+        }
+        other => {
+            if other != "ollama" {
+                println!("[llm] Unknown LLM_BACKEND '{}', using Ollama", other);
+            }
+            Arc::new(ollama::OllamaBackend::new(&OLLAMA_BASE_URL))
+        }
+    }
+}
+
+// Ollama reads keep_alive as seconds or a Go duration ("1h30m"); negative means
+// forever. Values it wouldn't understand count as its own default of five minutes.
+pub fn parse_keep_alive(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if value.starts_with('-') {
+        return None;
+    }
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Some(Duration::from_secs_f64(seconds));
+    }
+
+    let fallback = Some(Duration::from_secs(5 * 60));
+    let mut seconds = 0.0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let number_end = rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len());
+        let unit_end = rest[number_end..]
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .map_or(rest.len(), |end| number_end + end);
+        let Ok(number) = rest[..number_end].parse::<f64>() else {
+            return fallback;
+        };
+        let scale = match &rest[number_end..unit_end] {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 1e-3,
+            "us" | "µs" => 1e-6,
+            "ns" => 1e-9,
+            _ => return fallback,
+        };
+        seconds += number * scale;
+        rest = &rest[unit_end..];
+    }
+    Some(Duration::from_secs_f64(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_alive_reads_like_ollama() {
+        assert_eq!(parse_keep_alive("30m"), Some(Duration::from_secs(1800)));
+        assert_eq!(parse_keep_alive("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_keep_alive("300"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_keep_alive("0"), Some(Duration::ZERO));
+        assert_eq!(parse_keep_alive("-1"), None);
+        assert_eq!(parse_keep_alive("-1m"), None);
+        assert_eq!(parse_keep_alive("soon"), Some(Duration::from_secs(300)));
+    }
+}
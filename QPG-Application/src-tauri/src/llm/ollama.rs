@@ -0,0 +1,274 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+use ollama_rs::Ollama;
+use ollama_rs::generation::embeddings::request::{EmbeddingsInput, GenerateEmbeddingsRequest};
+use super::{MODEL_KEEP_ALIVE, ChatMessage, LlmBackend, LlmError, LlmReply, LlmRequest, OnContent, OnPullProgress, PullProgress, ToolCall};
+
+pub struct OllamaBackend {
+    base_url: String,
+    client: Client,
+    ollama: Ollama,
+}
+
+impl OllamaBackend {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: Client::new(),
+            ollama: Ollama::try_new(base_url).unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl LlmBackend for OllamaBackend {
+    fn name(&self) -> &'static str {
+        "Ollama"
+    }
+
+    // Calls /api/chat directly since ollama-rs has no tool support.
+    async fn chat_stream(
+        &self,
+        request: &LlmRequest,
+        on_content: &mut OnContent<'_>,
+    ) -> Result<LlmReply, LlmError> {
+        let mut body = json!({
+            "model": request.model,
+            "messages": request.messages,
+            "stream": true,
+            "options": { "num_ctx": request.window },
+            "keep_alive": keep_alive(),
+        });
+        if let Some(schema) = &request.format {
+            body["format"] = schema.clone();
+        }
+        if !request.tools.is_empty() {
+            body["tools"] = json!(request.tools);
+        }
+
+        let mut response = self
+            .client
+            .post(format!("{}/api/chat", self.base_url))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("HTTP request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            if text.contains("does not support tools") {
+                return Err(LlmError::ToolsUnsupported);
+            }
+            return Err(LlmError::Failed(format!("Ollama returned {}: {}", status, text)));
+        }
+
+        let mut reply = LlmReply {
+            model: request.model.clone(),
+            created_at: String::new(),
+            message: ChatMessage::assistant(String::new()),
+            done: false,
+            tool_calls: Vec::new(),
+        };
+        let mut buffer: Vec<u8> = Vec::new();
+        while let Some(bytes) = response.chunk().await.map_err(|e| format!("Failed to read reply: {}", e))? {
+            buffer.extend_from_slice(&bytes);
+            while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=newline).collect();
+                if read_line(&line, &mut reply)? {
+                    on_content(&reply.message.content);
+                }
+            }
+        }
+        if read_line(&buffer, &mut reply)? {
+            on_content(&reply.message.content);
+        }
+
+        if !reply.done {
+            return Err(LlmError::Failed("Generation ended before the model finished".to_string()));
+        }
+        Ok(reply)
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, String> {
+        self.ollama
+            .list_local_models()
+            .await
+            .map(|models| models.into_iter().map(|model| model.name).collect())
+            .map_err(|e| format!("Failed to list models: {}", e))
+    }
+
+    async fn embed(&self, model: &str, input: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+        self.ollama
+            .generate_embeddings(GenerateEmbeddingsRequest::new(
+                model.to_string(),
+                EmbeddingsInput::Multiple(input),
+            ))
+            .await
+            .map(|response| response.embeddings)
+            .map_err(|e| format!("Failed to generate embeddings: {}", e))
+    }
+
+    // ollama-rs drops `model_info` from /api/show, so this reads it directly. The length
+    // is stored under an architecture-specific key such as "llama.context_length"; a
+    // num_ctx in the Modelfile parameters takes precedence.
+    async fn context_length(&self, model: &str) -> Result<Option<usize>, String> {
+        let response = self
+            .client
+            .post(format!("{}/api/show", self.base_url))
+            .json(&json!({ "model": model }))
+            .send()
+            .await
+            .map_err(|e| format!("HTTP request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Ollama returned {}", response.status()));
+        }
+        let body: Value = response.json().await.map_err(|e| format!("Failed to parse /api/show: {}", e))?;
+
+        let num_ctx = body
+            .get("parameters")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .lines()
+            .find_map(|line| {
+                let mut parts = line.split_whitespace();
+                (parts.next() == Some("num_ctx")).then(|| parts.next()?.parse().ok())?
+            });
+        if num_ctx.is_some() {
+            return Ok(num_ctx);
+        }
+
+        Ok(body
+            .get("model_info")
+            .and_then(Value::as_object)
+            .and_then(|info| {
+                info.iter()
+                    .find(|(key, _)| key.ends_with(".context_length"))
+                    .and_then(|(_, value)| value.as_u64())
+            })
+            .map(|length| length as usize))
+    }
+
+    // A generate request without a prompt only loads the model.
+    async fn load_model(&self, model: &str) -> Result<(), String> {
+        let response = self
+            .client
+            .post(format!("{}/api/generate", self.base_url))
+            .json(&json!({ "model": model, "keep_alive": keep_alive() }))
+            .send()
+            .await
+            .map_err(|e| format!("HTTP request failed: {}", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("Ollama returned {}: {}", status, text));
+        }
+        Ok(())
+    }
+
+    // Reads /api/pull as it streams so progress can be reported during the download.
+    // Dropping the future closes the connection, which stops the download.
+    async fn pull_model(&self, model: &str, on_progress: &mut OnPullProgress<'_>) -> Result<(), String> {
+        let mut response = self
+            .client
+            .post(format!("{}/api/pull", self.base_url))
+            .json(&json!({ "model": model, "stream": true }))
+            .send()
+            .await
+            .map_err(|e| format!("HTTP request failed: {}", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("Ollama returned {}: {}", status, text));
+        }
+
+        let mut succeeded = false;
+        let mut buffer: Vec<u8> = Vec::new();
+        while let Some(bytes) = response.chunk().await.map_err(|e| format!("Failed to read pull status: {}", e))? {
+            buffer.extend_from_slice(&bytes);
+            while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=newline).collect();
+                succeeded |= read_pull_line(&line, on_progress)?;
+            }
+        }
+        succeeded |= read_pull_line(&buffer, on_progress)?;
+
+        if !succeeded {
+            return Err(format!("The download of {} ended before it finished", model));
+        }
+        Ok(())
+    }
+
+    async fn delete_model(&self, model: &str) -> Result<(), String> {
+        self.ollama
+            .delete_model(model.to_string())
+            .await
+            .map_err(|e| format!("Failed to delete model: {}", e))
+    }
+}
+
+// Ollama takes a duration string, or a number of seconds where -1 means forever.
+fn keep_alive() -> Value {
+    match MODEL_KEEP_ALIVE.parse::<i64>() {
+        Ok(seconds) => json!(seconds),
+        Err(_) => json!(*MODEL_KEEP_ALIVE),
+    }
+}
+
+// Returns true once Ollama reports the pull as done.
+fn read_pull_line(line: &[u8], on_progress: &mut OnPullProgress<'_>) -> Result<bool, String> {
+    let line = String::from_utf8_lossy(line);
+    if line.trim().is_empty() {
+        return Ok(false);
+    }
+
+    let value: Value = serde_json::from_str(line.trim())
+        .map_err(|e| format!("Unreadable pull status: {}", e))?;
+    if let Some(error) = value.get("error").and_then(Value::as_str) {
+        return Err(format!("Ollama error: {}", error));
+    }
+    let status = value.get("status").and_then(Value::as_str).unwrap_or_default().to_string();
+    let succeeded = status == "success";
+    on_progress(PullProgress {
+        status,
+        completed: value.get("completed").and_then(Value::as_u64),
+        total: value.get("total").and_then(Value::as_u64),
+    });
+    Ok(succeeded)
+}
+
+// Returns true when the line added reply text.
+fn read_line(line: &[u8], reply: &mut LlmReply) -> Result<bool, LlmError> {
+    let line = String::from_utf8_lossy(line);
+    if line.trim().is_empty() {
+        return Ok(false);
+    }
+
+    let value: Value = serde_json::from_str(line.trim())
+        .map_err(|e| format!("Unreadable reply chunk: {}", e))?;
+    if let Some(error) = value.get("error").and_then(Value::as_str) {
+        return Err(LlmError::Failed(format!("Ollama error: {}", error)));
+    }
+
+    if let Some(calls) = value.pointer("/message/tool_calls") {
+        match serde_json::from_value::<Vec<ToolCall>>(calls.clone()) {
+            Ok(calls) => reply.tool_calls.extend(calls),
+            Err(e) => println!("[llm] Skipping malformed tool calls: {}", e),
+        }
+    }
+    if let Some(model) = value.get("model").and_then(Value::as_str) {
+        reply.model = model.to_string();
+    }
+    if let Some(created_at) = value.get("created_at").and_then(Value::as_str) {
+        reply.created_at = created_at.to_string();
+    }
+    reply.done = value.get("done").and_then(Value::as_bool).unwrap_or(false);
+
+    let text = value
+        .pointer("/message/content")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    reply.message.content.push_str(text);
+    Ok(!text.is_empty())
+}
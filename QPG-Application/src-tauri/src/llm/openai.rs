@@ -0,0 +1,268 @@
+use std::collections::{BTreeMap, VecDeque};
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Value};
+use super::{ChatMessage, LlmBackend, LlmError, LlmReply, LlmRequest, MessageRole, OnContent, ToolCall, ToolCallFunction};
+
+// llama.cpp's server, vLLM, LM Studio and other servers that implement the OpenAI
+// chat completions API. The context window is fixed when the server starts, so the
+// one in the request is only used for budgeting the prompt.
+pub struct OpenAiBackend {
+    base_url: String,
+    api_key: Option<String>,
+    client: Client,
+}
+
+impl OpenAiBackend {
+    pub fn new(base_url: &str, api_key: Option<String>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            client: Client::new(),
+        }
+    }
+
+    fn authorized(&self, builder: RequestBuilder) -> RequestBuilder {
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key),
+            None => builder,
+        }
+    }
+
+    async fn models(&self) -> Result<Vec<Value>, String> {
+        let response = self
+            .authorized(self.client.get(format!("{}/models", self.base_url)))
+            .send()
+            .await
+            .map_err(|e| format!("HTTP request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Server returned {}", response.status()));
+        }
+        let body: Value = response.json().await.map_err(|e| format!("Failed to parse /models: {}", e))?;
+        Ok(body.get("data").and_then(Value::as_array).cloned().unwrap_or_default())
+    }
+}
+
+// These servers want an id on each tool call, its arguments as a JSON string, and the
+// id again on the tool message answering it. Ids are made up here, since tool
+// results always follow their calls in order.
+fn openai_messages(messages: &[ChatMessage]) -> Vec<Value> {
+    let mut unanswered: VecDeque<String> = VecDeque::new();
+    let mut next_id = 0;
+    let mut converted = Vec::with_capacity(messages.len());
+    for message in messages {
+        let mut value = json!({ "role": message.role, "content": message.content });
+        if !message.tool_calls.is_empty() {
+            let mut calls = Vec::new();
+            for call in &message.tool_calls {
+                let id = format!("call_{}", next_id);
+                next_id += 1;
+                unanswered.push_back(id.clone());
+                calls.push(json!({
+                    "id": id,
+                    "type": "function",
+                    "function": { "name": call.function.name, "arguments": call.function.arguments.to_string() },
+                }));
+            }
+            value["tool_calls"] = Value::Array(calls);
+        }
+        if message.role == MessageRole::Tool {
+            value["tool_call_id"] = json!(unanswered.pop_front().unwrap_or_default());
+        }
+        converted.push(value);
+    }
+    converted
+}
+
+// Tool call arguments arrive as a JSON string split across chunks, keyed by the
+// call's index.
+#[derive(Default)]
+struct PartialToolCall {
+    name: String,
+    arguments: String,
+}
+
+#[async_trait]
+impl LlmBackend for OpenAiBackend {
+    fn name(&self) -> &'static str {
+        "OpenAI-compatible"
+    }
+
+    async fn chat_stream(
+        &self,
+        request: &LlmRequest,
+        on_content: &mut OnContent<'_>,
+    ) -> Result<LlmReply, LlmError> {
+        let mut body = json!({
+            "model": request.model,
+            "messages": openai_messages(&request.messages),
+            "stream": true,
+        });
+        if let Some(schema) = &request.format {
+            body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": { "name": "reply", "schema": schema },
+            });
+        }
+        if !request.tools.is_empty() {
+            body["tools"] = json!(request.tools);
+        }
+
+        let mut response = self
+            .authorized(self.client.post(format!("{}/chat/completions", self.base_url)))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("HTTP request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            // Servers word this differently (llama.cpp wants --jinja for tools), but
+            // all of them mention tools when that's what they refused.
+            if !request.tools.is_empty() && status.is_client_error() && text.to_lowercase().contains("tool") {
+                return Err(LlmError::ToolsUnsupported);
+            }
+            return Err(LlmError::Failed(format!("Server returned {}: {}", status, text)));
+        }
+
+        let mut reply = LlmReply {
+            model: request.model.clone(),
+            created_at: String::new(),
+            message: ChatMessage::assistant(String::new()),
+            done: false,
+            tool_calls: Vec::new(),
+        };
+        let mut calls: BTreeMap<u64, PartialToolCall> = BTreeMap::new();
+        let mut buffer: Vec<u8> = Vec::new();
+        while let Some(bytes) = response.chunk().await.map_err(|e| format!("Failed to read reply: {}", e))? {
+            buffer.extend_from_slice(&bytes);
+            while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=newline).collect();
+                if read_event(&line, &mut reply, &mut calls)? {
+                    on_content(&reply.message.content);
+                }
+            }
+        }
+        if read_event(&buffer, &mut reply, &mut calls)? {
+            on_content(&reply.message.content);
+        }
+
+        if !reply.done {
+            return Err(LlmError::Failed("Generation ended before the model finished".to_string()));
+        }
+        reply.tool_calls = calls
+            .into_values()
+            .map(|call| ToolCall {
+                function: ToolCallFunction {
+                    arguments: serde_json::from_str(&call.arguments).unwrap_or(Value::Null),
+                    name: call.name,
+                },
+            })
+            .collect();
+        Ok(reply)
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, String> {
+        Ok(self
+            .models()
+            .await
+            .map_err(|e| format!("Failed to list models: {}", e))?
+            .iter()
+            .filter_map(|model| model.get("id").and_then(Value::as_str))
+            .map(str::to_string)
+            .collect())
+    }
+
+    async fn embed(&self, model: &str, input: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+        let response = self
+            .authorized(self.client.post(format!("{}/embeddings", self.base_url)))
+            .json(&json!({ "model": model, "input": input }))
+            .send()
+            .await
+            .map_err(|e| format!("HTTP request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Failed to generate embeddings: server returned {}", response.status()));
+        }
+        let body: Value = response.json().await.map_err(|e| format!("Failed to parse /embeddings: {}", e))?;
+
+        let mut data: Vec<(u64, Vec<f32>)> = body
+            .get("data")
+            .and_then(Value::as_array)
+            .ok_or("Embedding reply has no data")?
+            .iter()
+            .filter_map(|item| {
+                let index = item.get("index").and_then(Value::as_u64).unwrap_or_default();
+                let vector = serde_json::from_value(item.get("embedding")?.clone()).ok()?;
+                Some((index, vector))
+            })
+            .collect();
+        data.sort_by_key(|(index, _)| *index);
+        Ok(data.into_iter().map(|(_, vector)| vector).collect())
+    }
+
+    // llama.cpp lists the training context under "meta"; other servers don't say.
+    async fn context_length(&self, model: &str) -> Result<Option<usize>, String> {
+        Ok(self
+            .models()
+            .await?
+            .iter()
+            .find(|entry| entry.get("id").and_then(Value::as_str) == Some(model))
+            .and_then(|entry| entry.pointer("/meta/n_ctx_train"))
+            .and_then(Value::as_u64)
+            .map(|length| length as usize))
+    }
+}
+
+// Returns true when the event added reply text.
+fn read_event(
+    line: &[u8],
+    reply: &mut LlmReply,
+    calls: &mut BTreeMap<u64, PartialToolCall>,
+) -> Result<bool, LlmError> {
+    let line = String::from_utf8_lossy(line);
+    let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+        return Ok(false);
+    };
+    if data == "[DONE]" {
+        reply.done = true;
+        return Ok(false);
+    }
+
+    let value: Value = serde_json::from_str(data)
+        .map_err(|e| format!("Unreadable reply chunk: {}", e))?;
+    if let Some(error) = value.get("error") {
+        let message = error.get("message").and_then(Value::as_str).map_or_else(|| error.to_string(), str::to_string);
+        return Err(LlmError::Failed(format!("Server error: {}", message)));
+    }
+    if let Some(model) = value.get("model").and_then(Value::as_str) {
+        reply.model = model.to_string();
+    }
+    if let Some(created) = value.get("created").and_then(Value::as_u64) {
+        reply.created_at = created.to_string();
+    }
+
+    let Some(choice) = value.pointer("/choices/0") else {
+        return Ok(false);
+    };
+    if choice.get("finish_reason").is_some_and(|reason| !reason.is_null()) {
+        reply.done = true;
+    }
+    for call in choice.pointer("/delta/tool_calls").and_then(Value::as_array).into_iter().flatten() {
+        let index = call.get("index").and_then(Value::as_u64).unwrap_or_default();
+        let partial = calls.entry(index).or_default();
+        if let Some(name) = call.pointer("/function/name").and_then(Value::as_str) {
+            partial.name.push_str(name);
+        }
+        if let Some(arguments) = call.pointer("/function/arguments").and_then(Value::as_str) {
+            partial.arguments.push_str(arguments);
+        }
+    }
+
+    let text = choice
+        .pointer("/delta/content")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    reply.message.content.push_str(text);
+    Ok(!text.is_empty())
+}
@@ -0,0 +1,280 @@
+use std::env;
+use rust_stemmers::{Algorithm, Stemmer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    English,
+    Spanish,
+    German,
+    French,
+}
+
+const ENGLISH_STOPWORDS: &[&str] = &[
+    "the", "is", "to", "a", "and", "for", "on", "in", "of", "with",
+    "set", "enable", "disable", "my", "i", "me", "it", "can", "could", "you",
+    "please", "be", "too", "so", "this", "that", "what", "how", "want", "like",
+    "turn", "off", "make", "up", "down", "an", "are", "t", "s", "don", "also", "known", "as",
+];
+const SPANISH_STOPWORDS: &[&str] = &[
+    "el", "la", "los", "las", "un", "una", "de", "del", "en", "y", "a", "al", "que",
+    "por", "para", "con", "mi", "mis", "me", "es", "lo", "se", "no", "muy", "más",
+    "favor", "puedes", "quiero", "activa", "activar", "desactiva", "desactivar",
+    "pon", "poner", "haz", "hacer", "también", "conocido", "como",
+];
+const GERMAN_STOPWORDS: &[&str] = &[
+    "der", "die", "das", "den", "dem", "ein", "eine", "einen", "und", "zu", "in", "im",
+    "auf", "für", "mit", "von", "mein", "meine", "meinen", "ich", "mir", "mich", "es",
+    "ist", "bitte", "kannst", "du", "nicht", "sehr", "mach", "machen", "schalte",
+    "schalten", "aus", "an", "aktivieren", "deaktivieren", "auch", "bekannt", "als",
+];
+const FRENCH_STOPWORDS: &[&str] = &[
+    "le", "la", "les", "l", "un", "une", "de", "des", "du", "d", "et", "à", "au", "aux",
+    "en", "pour", "avec", "sur", "mon", "ma", "mes", "je", "j", "me", "m", "est", "ce",
+    "c", "s", "il", "plus", "trop", "très", "peux", "tu", "vous", "stp", "plaît",
+    "active", "activer", "désactive", "désactiver", "mets", "mettre", "aussi", "appelé",
+];
+
+impl Language {
+    // Accepts BCP 47 tags from the webview ("es-MX") as well as POSIX locales from the
+    // environment ("de_DE.UTF-8"). Anything unrecognised is treated as English.
+    pub fn from_locale(locale: &str) -> Self {
+        let code = locale
+            .split(['-', '_', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        match code.as_str() {
+            "es" => Language::Spanish,
+            "de" => Language::German,
+            "fr" => Language::French,
+            _ => Language::English,
+        }
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Spanish => "es",
+            Language::German => "de",
+            Language::French => "fr",
+        }
+    }
+
+    pub fn stemmer(self) -> Stemmer {
+        Stemmer::create(match self {
+            Language::English => Algorithm::English,
+            Language::Spanish => Algorithm::Spanish,
+            Language::German => Algorithm::German,
+            Language::French => Algorithm::French,
+        })
+    }
+
+    pub fn stopwords(self) -> &'static [&'static str] {
+        match self {
+            Language::English => ENGLISH_STOPWORDS,
+            Language::Spanish => SPANISH_STOPWORDS,
+            Language::German => GERMAN_STOPWORDS,
+            Language::French => FRENCH_STOPWORDS,
+        }
+    }
+
+    pub fn also_known_as(self) -> &'static str {
+        match self {
+            Language::English => "Also known as",
+            Language::Spanish => "También conocido como",
+            Language::German => "Auch bekannt als",
+            Language::French => "Aussi appelé",
+        }
+    }
+
+    // Appended to the system prompt in the user's own language. The JSON keys, setting
+    // names and commands have to stay as they are for the reply to be parsed.
+    pub fn prompt_instructions(self) -> &'static str {
+        match self {
+            Language::English => "The user speaks English. Write \"message\" in English.",
+            Language::Spanish => "El usuario habla español. Escribe siempre \"message\" en español, pero deja las claves JSON, los nombres de los ajustes y los comandos exactamente como aparecen en el JSON de referencia.",
+            Language::German => "Der Benutzer spricht Deutsch. Schreibe \"message\" immer auf Deutsch, aber lass die JSON-Schlüssel, die Namen der Einstellungen und die Befehle genau so, wie sie im Referenz-JSON stehen.",
+            Language::French => "L'utilisateur parle français. Rédige toujours \"message\" en français, mais laisse les clés JSON, les noms des réglages et les commandes exactement comme dans le JSON de référence.",
+        }
+    }
+
+    pub fn can_change(self, settings: &str) -> String {
+        match self {
+            Language::English => format!("I can change {} for you.", settings),
+            Language::Spanish => format!("Puedo cambiar {} por ti.", settings),
+            Language::German => format!("Ich kann {} für dich ändern.", settings),
+            Language::French => format!("Je peux modifier {} pour toi.", settings),
+        }
+    }
+
+    pub fn can_switch_profile(self, name: &str) -> String {
+        match self {
+            Language::English => format!("I can switch to your {} profile.", name),
+            Language::Spanish => format!("Puedo cambiar a tu perfil {}.", name),
+            Language::German => format!("Ich kann zu deinem Profil {} wechseln.", name),
+            Language::French => format!("Je peux passer à ton profil {}.", name),
+        }
+    }
+
+    pub fn kept_changes(self, count: usize) -> String {
+        match (self, count) {
+            (Language::English, 1) => "I kept the change.".to_string(),
+            (Language::English, _) => format!("I kept {} changes.", count),
+            (Language::Spanish, 1) => "Conservé el cambio.".to_string(),
+            (Language::Spanish, _) => format!("Conservé {} cambios.", count),
+            (Language::German, 1) => "Ich habe die Änderung behalten.".to_string(),
+            (Language::German, _) => format!("Ich habe {} Änderungen behalten.", count),
+            (Language::French, 1) => "J'ai gardé la modification.".to_string(),
+            (Language::French, _) => format!("J'ai gardé {} modifications.", count),
+        }
+    }
+
+    pub fn reverted_changes(self, count: usize) -> String {
+        match (self, count) {
+            (Language::English, 1) => "I put the change back.".to_string(),
+            (Language::English, _) => format!("I put {} changes back.", count),
+            (Language::Spanish, 1) => "Deshice el cambio.".to_string(),
+            (Language::Spanish, _) => format!("Deshice {} cambios.", count),
+            (Language::German, 1) => "Ich habe die Änderung zurückgenommen.".to_string(),
+            (Language::German, _) => format!("Ich habe {} Änderungen zurückgenommen.", count),
+            (Language::French, 1) => "J'ai annulé la modification.".to_string(),
+            (Language::French, _) => format!("J'ai annulé {} modifications.", count),
+        }
+    }
+
+    // Effects in action previews. `name` is the setting's display name.
+    pub fn turns_on(self, name: &str) -> String {
+        match self {
+            Language::English => format!("{} will be turned on.", name),
+            Language::Spanish => format!("{} se activará.", name),
+            Language::German => format!("{} wird eingeschaltet.", name),
+            Language::French => format!("{} sera activé.", name),
+        }
+    }
+
+    pub fn turns_off(self, name: &str) -> String {
+        match self {
+            Language::English => format!("{} will be turned off.", name),
+            Language::Spanish => format!("{} se desactivará.", name),
+            Language::German => format!("{} wird ausgeschaltet.", name),
+            Language::French => format!("{} sera désactivé.", name),
+        }
+    }
+
+    pub fn goes_up(self, name: &str, old: &str, new: &str) -> String {
+        match self {
+            Language::English => format!("{} will go up from {} to {}.", name, old, new),
+            Language::Spanish => format!("{} subirá de {} a {}.", name, old, new),
+            Language::German => format!("{} steigt von {} auf {}.", name, old, new),
+            Language::French => format!("{} augmentera de {} à {}.", name, old, new),
+        }
+    }
+
+    pub fn goes_down(self, name: &str, old: &str, new: &str) -> String {
+        match self {
+            Language::English => format!("{} will go down from {} to {}.", name, old, new),
+            Language::Spanish => format!("{} bajará de {} a {}.", name, old, new),
+            Language::German => format!("{} sinkt von {} auf {}.", name, old, new),
+            Language::French => format!("{} diminuera de {} à {}.", name, old, new),
+        }
+    }
+
+    pub fn changes_from(self, name: &str, old: &str, new: &str) -> String {
+        match self {
+            Language::English => format!("{} will change from {} to {}.", name, old, new),
+            Language::Spanish => format!("{} cambiará de {} a {}.", name, old, new),
+            Language::German => format!("{} ändert sich von {} auf {}.", name, old, new),
+            Language::French => format!("{} passera de {} à {}.", name, old, new),
+        }
+    }
+
+    pub fn already(self, name: &str, value: &str) -> String {
+        match self {
+            Language::English => format!("{} is already {}, so nothing will change.", name, value),
+            Language::Spanish => format!("{} ya está en {}, así que no cambiará nada.", name, value),
+            Language::German => format!("{} steht bereits auf {}, es ändert sich also nichts.", name, value),
+            Language::French => format!("{} est déjà à {}, rien ne changera donc.", name, value),
+        }
+    }
+
+    pub fn reverts_unless_kept(self, secs: u64) -> String {
+        match self {
+            Language::English => format!("It is undone after {} seconds unless you keep it.", secs),
+            Language::Spanish => format!("Se deshará a los {} segundos si no lo conservas.", secs),
+            Language::German => format!("Die Änderung wird nach {} Sekunden zurückgenommen, wenn du sie nicht behältst.", secs),
+            Language::French => format!("Ce changement sera annulé après {} secondes si tu ne le gardes pas.", secs),
+        }
+    }
+
+    // Shown when only the rule-based parser is available and it couldn't read the request.
+    pub fn rules_only_hint(self) -> &'static str {
+        match self {
+            Language::English => "No language model is available right now, so I can only handle short requests like \"set zoom to 1.5\" or \"turn on the screen reader\".",
+            Language::Spanish => "No hay ningún modelo de lenguaje disponible ahora, así que solo entiendo peticiones cortas como \"pon el zoom en 1,5\" o \"activa el lector de pantalla\".",
+            Language::German => "Gerade ist kein Sprachmodell verfügbar, daher verstehe ich nur kurze Anfragen wie \"Zoom auf 1,5\" oder \"Bildschirmleser einschalten\".",
+            Language::French => "Aucun modèle de langage n'est disponible pour le moment, je ne comprends donc que des demandes courtes comme \"zoom à 1,5\" ou \"active le lecteur d'écran\".",
+        }
+    }
+
+    pub fn currently(self, setting: &str, live: &str) -> String {
+        match self {
+            Language::English => format!("Your {} is currently {}.", setting, live),
+            Language::Spanish => format!("Tu ajuste {} está actualmente en {}.", setting, live),
+            Language::German => format!("Deine Einstellung {} steht derzeit auf {}.", setting, live),
+            Language::French => format!("Ton réglage {} est actuellement à {}.", setting, live),
+        }
+    }
+
+    pub fn changed_outside(self, setting: &str, live: &str, stored: &str) -> String {
+        match self {
+            Language::English => format!(
+                "Your {} is currently {}. Note that your saved preference is {}, so it was changed outside the assistant.",
+                setting, live, stored
+            ),
+            Language::Spanish => format!(
+                "Tu ajuste {} está actualmente en {}. Tu preferencia guardada es {}, así que se cambió fuera del asistente.",
+                setting, live, stored
+            ),
+            Language::German => format!(
+                "Deine Einstellung {} steht derzeit auf {}. Gespeichert ist {}, sie wurde also außerhalb des Assistenten geändert.",
+                setting, live, stored
+            ),
+            Language::French => format!(
+                "Ton réglage {} est actuellement à {}. Ta préférence enregistrée est {}, il a donc été modifié en dehors de l'assistant.",
+                setting, live, stored
+            ),
+        }
+    }
+
+    pub fn unreadable(self, setting: &str, stored: &str) -> String {
+        match self {
+            Language::English => format!(
+                "I couldn't read your {} from the system; your saved preference is {}.",
+                setting, stored
+            ),
+            Language::Spanish => format!(
+                "No pude leer tu ajuste {} del sistema; tu preferencia guardada es {}.",
+                setting, stored
+            ),
+            Language::German => format!(
+                "Ich konnte deine Einstellung {} nicht vom System lesen; gespeichert ist {}.",
+                setting, stored
+            ),
+            Language::French => format!(
+                "Je n'ai pas pu lire ton réglage {} depuis le système ; ta préférence enregistrée est {}.",
+                setting, stored
+            ),
+        }
+    }
+}
+
+// Used when the frontend doesn't send a locale.
+pub fn system_language() -> Language {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| Language::from_locale(&value))
+        .unwrap_or_default()
+}
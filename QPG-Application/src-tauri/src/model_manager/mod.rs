@@ -0,0 +1,200 @@
+use std::fs;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+use crate::intent::{self, RulesMode};
+use crate::llm::{LlmBackend, PullProgress, DEFAULT_MODEL, MODEL_KEEP_ALIVE_DURATION};
+use crate::models::{ModelPullEvent, ModelRecommendation, ModelStatus, ModelStatusEvent};
+use crate::platform;
+use crate::state::GenerateState;
+
+const SELECTED_MODEL_FILE: &str = "selected_model";
+
+// Smallest amount of free memory (MiB) each model runs comfortably in, largest first.
+const MODEL_TIERS: &[(u64, &str)] = &[
+    (12_288, "granite3-dense:8b"),
+    (6_144, "granite3-dense:2b"),
+    (0, "granite3-moe:1b"),
+];
+
+// Downloads run as background tasks so the caller isn't blocked for minutes; progress
+// is reported through "model-pull-progress" events and cancel_pull stops the download.
+pub async fn start_pull(app_handle: &AppHandle, llm: Arc<dyn LlmBackend>, model: &str) -> Result<(), String> {
+    let state = app_handle.state::<GenerateState>();
+    let Some(cancel) = state.begin_pull(model).await else {
+        return Err(format!("{} is already being downloaded", model));
+    };
+
+    let app_handle = app_handle.clone();
+    let model = model.to_string();
+    tauri::async_runtime::spawn(async move {
+        println!("[models] Pulling {}", model);
+        let mut last_reported = (String::new(), None);
+        let mut on_progress = |progress: PullProgress| {
+            // Ollama reports every few kilobytes; one event per status and percent is enough.
+            let percent = progress.completed.zip(progress.total).map(|(done, total)| done * 100 / total.max(1));
+            if (progress.status.as_str(), percent) == (last_reported.0.as_str(), last_reported.1) {
+                return;
+            }
+            last_reported = (progress.status.clone(), percent);
+            emit_progress(&app_handle, ModelPullEvent {
+                model: model.clone(),
+                status: progress.status,
+                completed: progress.completed,
+                total: progress.total,
+                done: false,
+                error: None,
+            });
+        };
+
+        let result = tokio::select! {
+            _ = cancel.notified() => Err("Download cancelled".to_string()),
+            result = llm.pull_model(&model, &mut on_progress) => result,
+        };
+        app_handle.state::<GenerateState>().end_pull(&model, &cancel).await;
+
+        match &result {
+            Ok(()) => println!("[models] Pulled {}", model),
+            Err(e) => println!("[models] Pulling {} failed: {}", model, e),
+        }
+        emit_progress(&app_handle, ModelPullEvent {
+            model: model.clone(),
+            status: if result.is_ok() { "success".to_string() } else { "failed".to_string() },
+            completed: None,
+            total: None,
+            done: true,
+            error: result.err(),
+        });
+    });
+    Ok(())
+}
+
+fn emit_progress(app_handle: &AppHandle, event: ModelPullEvent) {
+    if let Err(e) = app_handle.emit("model-pull-progress", event) {
+        println!("[models] Failed to emit pull progress: {}", e);
+    }
+}
+
+// Picks the largest model that fits in the memory free right now, falling back to the
+// total when the platform doesn't report free memory. Without either, the smallest
+// model is suggested.
+pub async fn recommend_model(llm: &dyn LlmBackend) -> ModelRecommendation {
+    let memory = tauri::async_runtime::spawn_blocking(platform::memory_mb).await.ok().flatten();
+    let total_ram_mb = memory.map(|(total, _)| total);
+    let available_ram_mb = memory.and_then(|(_, available)| available);
+    let usable = available_ram_mb.or(total_ram_mb).unwrap_or_default();
+
+    let model = MODEL_TIERS
+        .iter()
+        .find(|(needed, _)| usable >= *needed)
+        .map_or(MODEL_TIERS[MODEL_TIERS.len() - 1].1, |(_, model)| *model)
+        .to_string();
+    let installed = llm
+        .list_models()
+        .await
+        .map(|models| models.iter().any(|installed| installed == &model))
+        .unwrap_or(false);
+
+    ModelRecommendation {
+        model,
+        total_ram_mb,
+        available_ram_mb,
+        installed,
+    }
+}
+
+pub async fn set_status(app_handle: &AppHandle, model: &str, status: ModelStatus, error: Option<String>) {
+    app_handle.state::<GenerateState>().set_model_status(model, status).await;
+    emit_status(app_handle, model, status, error);
+    if status == ModelStatus::Ready {
+        mark_used(Some(app_handle), &app_handle.state::<GenerateState>(), model).await;
+    }
+}
+
+// Ollama unloads a model that has sat idle for MODEL_KEEP_ALIVE. The status follows it
+// back to unloaded, so the next message shows the model loading again.
+pub async fn mark_used(app_handle: Option<&AppHandle>, state: &GenerateState, model: &str) {
+    let Some(keep_alive) = *MODEL_KEEP_ALIVE_DURATION else {
+        return;
+    };
+    if model == intent::RULES_MODEL {
+        return;
+    }
+    state.touch_model(model, keep_alive).await;
+
+    let Some(app_handle) = app_handle.cloned() else {
+        return;
+    };
+    let model = model.to_string();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(keep_alive).await;
+        if app_handle.state::<GenerateState>().expire_model(&model).await {
+            println!("[models] {} has been idle past its keep-alive and is unloaded", model);
+            emit_status(&app_handle, &model, ModelStatus::Unloaded, None);
+        }
+    });
+}
+
+fn emit_status(app_handle: &AppHandle, model: &str, status: ModelStatus, error: Option<String>) {
+    let event = ModelStatusEvent { model: model.to_string(), status, error };
+    if let Err(e) = app_handle.emit("model-status", event) {
+        println!("[models] Failed to emit model status: {}", e);
+    }
+}
+
+// Loads the model in the background so the first message doesn't wait for it. The
+// rule-based parser has nothing to load.
+pub async fn warm_up(app_handle: &AppHandle, llm: Arc<dyn LlmBackend>, model: &str) {
+    if model == intent::RULES_MODEL {
+        set_status(app_handle, model, ModelStatus::Ready, None).await;
+        return;
+    }
+    if app_handle.state::<GenerateState>().get_model_status(model).await == ModelStatus::Loading {
+        return;
+    }
+    set_status(app_handle, model, ModelStatus::Loading, None).await;
+
+    let app_handle = app_handle.clone();
+    let model = model.to_string();
+    tauri::async_runtime::spawn(async move {
+        println!("[models] Loading {}", model);
+        match llm.load_model(&model).await {
+            Ok(()) => {
+                println!("[models] {} is ready", model);
+                set_status(&app_handle, &model, ModelStatus::Ready, None).await;
+            }
+            Err(e) => {
+                println!("[models] Loading {} failed: {}", model, e);
+                set_status(&app_handle, &model, ModelStatus::Failed, Some(e)).await;
+            }
+        }
+    });
+}
+
+// On launch, the model picked last time (or DEFAULT_MODEL) is loaded if it's installed.
+pub async fn preload_on_startup(app_handle: &AppHandle, llm: Arc<dyn LlmBackend>) {
+    if *intent::INTENT_RULES == RulesMode::Only {
+        return;
+    }
+    let model = selected_model(app_handle).unwrap_or_else(|| DEFAULT_MODEL.clone());
+    match llm.list_models().await {
+        Ok(models) if models.contains(&model) => warm_up(app_handle, llm, &model).await,
+        Ok(_) => println!("[models] {} is not installed, skipping preload", model),
+        Err(e) => println!("[models] Skipping preload: {}", e),
+    }
+}
+
+fn selected_model(app_handle: &AppHandle) -> Option<String> {
+    let dir = app_handle.path().app_data_dir().ok()?;
+    let model = fs::read_to_string(dir.join(SELECTED_MODEL_FILE)).ok()?;
+    Some(model.trim().to_string()).filter(|model| !model.is_empty())
+}
+
+pub fn remember_selected_model(app_handle: &AppHandle, model: &str) -> Result<(), String> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("No app data directory: {}", e))?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(SELECTED_MODEL_FILE);
+    fs::write(&path, model).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
@@ -1,24 +1,180 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use ollama_rs::generation::chat::{ChatMessageResponse};
+use crate::llm::{ChatMessage, LlmReply};
+use crate::locale::Language;
 
 #[derive(Debug, Deserialize)]
 pub struct ChatRequest {
     pub model: String,
     pub prompt: String,
     pub chat_id: String,
+    // Set when the user picked one of the settings offered in a clarifying question.
+    #[serde(default)]
+    pub setting: Option<String>,
+    // BCP 47 tag from the webview, e.g. "es-MX".
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+impl ChatRequest {
+    pub fn language(&self) -> Language {
+        match &self.locale {
+            Some(locale) => Language::from_locale(locale),
+            None => crate::locale::system_language(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplyKind {
+    #[default]
+    Action,
+    Clarify,
+    Query,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct ModelResponse {
     pub message: String,
-    pub command: String,
+    #[serde(default)]
+    pub kind: ReplyKind,
+    #[serde(default)]
+    pub commands: Vec<String>,
+    #[serde(default)]
+    pub choices: Vec<String>,
+    #[serde(default)]
+    pub queries: Vec<String>,
+    // Replies in the older single-command format are still accepted.
+    #[serde(default, skip_serializing)]
+    #[schemars(skip)]
+    pub command: Option<String>,
+}
+
+impl ModelResponse {
+    pub fn all_commands(&self) -> Vec<String> {
+        self.commands
+            .iter()
+            .chain(self.command.iter())
+            .map(|command| command.trim().to_string())
+            .filter(|command| !command.is_empty())
+            .collect()
+    }
 }
 
 #[derive(Serialize)]
 pub struct GenerateResult {
-    pub ollama_response: ChatMessageResponse,
+    // Named for Ollama, which the frontend was written against, whatever the backend.
+    pub ollama_response: LlmReply,
     pub command: Option<String>,
+    pub actions: Vec<SettingAction>,
+    pub kind: ReplyKind,
+    pub choices: Vec<String>,
+    pub readings: Vec<SettingReading>,
+    pub matches: Vec<SettingMatch>,
+    pub low_confidence: bool,
+    // A profile the user asked to switch to; the frontend activates it once confirmed.
+    pub profile: Option<String>,
+}
+
+impl GenerateResult {
+    pub fn new(ollama_response: LlmReply) -> Self {
+        GenerateResult {
+            ollama_response,
+            command: None,
+            actions: Vec::new(),
+            kind: ReplyKind::Action,
+            choices: Vec::new(),
+            readings: Vec::new(),
+            matches: Vec::new(),
+            low_confidence: false,
+            profile: None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SettingMatch {
+    pub setting: String,
+    pub score: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SettingReading {
+    pub setting: String,
+    pub live: Option<DefaultValue>,
+    pub stored: DefaultValue,
+    pub differs: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SettingAction {
+    pub setting: String,
+    pub command: String,
+    pub value: DefaultValue,
+    // Filled in before the action is shown; see preview::attach.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<ActionPreview>,
+}
+
+// What an action will do, worded for someone who can't read the command.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ActionPreview {
+    // Sent back by the frontend when the user approves this action; see preview::check_approval.
+    pub id: String,
+    pub display_name: String,
+    pub old_value: DefaultValue,
+    pub new_value: DefaultValue,
+    pub effect: String,
+    // The change is applied provisionally and put back unless the user keeps it.
+    pub auto_revertible: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GenerateChunk {
+    pub chat_id: String,
+    pub message: String,
+}
+
+// Emitted as "model-pull-progress" while a model downloads, and once more with `done`
+// set when it finishes, fails or is cancelled.
+#[derive(Serialize, Clone, Debug)]
+pub struct ModelPullEvent {
+    pub model: String,
+    pub status: String,
+    pub completed: Option<u64>,
+    pub total: Option<u64>,
+    pub done: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelStatus {
+    #[default]
+    Unloaded,
+    Loading,
+    Ready,
+    Failed,
+}
+
+// Emitted as "model-status" whenever a model starts or finishes loading.
+#[derive(Serialize, Clone, Debug)]
+pub struct ModelStatusEvent {
+    pub model: String,
+    pub status: ModelStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ModelRecommendation {
+    pub model: String,
+    pub total_ram_mb: Option<u64>,
+    pub available_ram_mb: Option<u64>,
+    pub installed: bool,
 }
 
 pub type AppConfig = HashMap<String, Setting>;
@@ -29,9 +185,57 @@ pub struct Setting {
     pub lower_bound: Option<f32>,
     #[serde(default)]
     pub upper_bound: Option<f32>,
+    // How far "a bit bigger" moves a numeric setting; see intent::parse.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<f32>,
     pub current: DefaultValue,
     #[serde(default)]
     pub commands: Commands,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platforms: Option<PlatformSupport>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub requires_confirmation: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub synonyms: Vec<String>,
+    // Localized description and synonyms keyed by language code ("es", "de", ...).
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub translations: HashMap<String, SettingText>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct SettingText {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub synonyms: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct PlatformSupport {
+    #[serde(default)]
+    pub session_types: Vec<String>,
+    #[serde(default)]
+    pub min_desktop_version: Option<String>,
+    #[serde(default)]
+    pub max_desktop_version: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct PlatformInfo {
+    pub env: String,
+    pub desktop: String,
+    pub session_type: String,
+    pub desktop_version: Option<String>,
+    pub distro: Option<String>,
+    pub schemas: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -44,7 +248,7 @@ pub struct Commands {
     pub gnome: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum DefaultValue {
     Float(f32),
@@ -52,8 +256,130 @@ pub enum DefaultValue {
     String(String),
 }
 
+impl std::fmt::Display for DefaultValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DefaultValue::Float(v) => write!(f, "{}", v),
+            DefaultValue::Bool(b) => write!(f, "{}", b),
+            DefaultValue::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct UpdateJSONPreferencesRequest {
     pub username: String,
     pub preferences: AppConfig,
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub values: HashMap<String, DefaultValue>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UpdateJSONProfilesRequest {
+    pub username: String,
+    pub profiles: HashMap<String, Profile>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SettingDiff {
+    pub key: String,
+    pub current: Option<DefaultValue>,
+    pub target: DefaultValue,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Preset {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub version: u32,
+    #[serde(default)]
+    pub needs: Vec<String>,
+    #[serde(default)]
+    pub values: HashMap<String, DefaultValue>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeSource {
+    Chat,
+    Startup,
+    // Noticed when a live value read back differs from the last one the app knew.
+    External,
+    Profile,
+    Preset,
+    Restore,
+    Undo,
+    Redo,
+    Rollback,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChangeRecord {
+    pub id: u64,
+    pub setting: String,
+    pub command: String,
+    pub old_value: Option<DefaultValue>,
+    pub new_value: DefaultValue,
+    pub source: ChangeSource,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PendingChange {
+    pub id: String,
+    pub setting: String,
+    pub command: String,
+    pub old_value: DefaultValue,
+    pub new_value: DefaultValue,
+    pub source: ChangeSource,
+    pub timeout_secs: u64,
+    #[serde(skip)]
+    pub update: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChatSession {
+    pub id: String,
+    pub title: String,
+    pub model: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    // What the user saw, for showing the chat again.
+    pub transcript: Vec<TranscriptEntry>,
+    // What the model saw, including the system prompt and setting snippets, for
+    // restoring its context.
+    #[serde(default)]
+    pub messages: Vec<ChatMessage>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TranscriptEntry {
+    pub sender: String,
+    pub text: String,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ChatSummary {
+    pub id: String,
+    pub title: String,
+    pub model: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub message_count: usize,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Markdown,
+    Json,
+}
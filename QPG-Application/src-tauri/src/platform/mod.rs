@@ -0,0 +1,220 @@
+use std::process::Command;
+use crate::models::{PlatformInfo, PlatformSupport};
+
+pub fn detect_platform() -> PlatformInfo {
+    let env = detect_env();
+    let desktop = detect_desktop();
+    let session_type = detect_session_type();
+    let desktop_version = detect_desktop_version(&env);
+    let distro = detect_distro();
+    let schemas = if env.contains("gnome") {
+        list_gsettings_schemas()
+    } else {
+        Vec::new()
+    };
+
+    PlatformInfo {
+        env,
+        desktop,
+        session_type,
+        desktop_version,
+        distro,
+        schemas,
+    }
+}
+
+pub fn detect_env() -> String {
+    #[cfg(target_os = "macos")]
+    let env = "macos".to_string();
+    #[cfg(target_os = "windows")]
+    let env = "windows".to_string();
+    #[cfg(target_os = "linux")]
+    let env = match get_linux_gui() {
+        Some(env) if env.to_lowercase().contains("gnome") => "gnome".to_string(),
+        Some(env) => format!("linux-{env}"),
+        None => "linux-unknown".to_string(),
+    };
+    env
+}
+
+#[cfg(target_os = "linux")]
+fn get_linux_gui() -> Option<String> {
+    if let Ok(desktop) = std::env::var("XDG_CURRENT_DESKTOP") {
+        if desktop.to_lowercase().contains("gnome") {
+            return Some("gnome".to_string());
+        }
+        return Some(desktop);
+    }
+
+    if let Ok(session) = std::env::var("DESKTOP_SESSION") {
+        if session.to_lowercase().contains("gnome") {
+            return Some("gnome".to_string());
+        }
+        return Some(session);
+    }
+
+    None
+}
+
+fn detect_desktop() -> String {
+    if cfg!(target_os = "macos") {
+        return "aqua".to_string();
+    }
+    if cfg!(target_os = "windows") {
+        return "explorer".to_string();
+    }
+    std::env::var("XDG_CURRENT_DESKTOP")
+        .or_else(|_| std::env::var("DESKTOP_SESSION"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn detect_session_type() -> String {
+    if cfg!(target_os = "macos") {
+        return "quartz".to_string();
+    }
+    if cfg!(target_os = "windows") {
+        return "windows".to_string();
+    }
+
+    if let Ok(session) = std::env::var("XDG_SESSION_TYPE") {
+        let session = session.trim().to_lowercase();
+        if !session.is_empty() {
+            return session;
+        }
+    }
+    if std::env::var("WAYLAND_DISPLAY").is_ok() {
+        return "wayland".to_string();
+    }
+    if std::env::var("DISPLAY").is_ok() {
+        return "x11".to_string();
+    }
+    "unknown".to_string()
+}
+
+fn detect_desktop_version(env: &str) -> Option<String> {
+    let (program, args): (&str, &[&str]) = match env {
+        "macos" => ("sw_vers", &["-productVersion"]),
+        s if s.contains("gnome") => ("gnome-shell", &["--version"]),
+        s if s.to_lowercase().contains("kde") => ("plasmashell", &["--version"]),
+        _ => return None,
+    };
+
+    let output = run_for_stdout(program, args)?;
+    output
+        .split_whitespace()
+        .find(|part| part.chars().next().is_some_and(|c| c.is_ascii_digit()))
+        .map(|version| version.to_string())
+}
+
+fn detect_distro() -> Option<String> {
+    if cfg!(target_os = "macos") {
+        return Some("macOS".to_string());
+    }
+    if cfg!(target_os = "windows") {
+        return Some("Windows".to_string());
+    }
+
+    let os_release = std::fs::read_to_string("/etc/os-release").ok()?;
+    let mut fallback = None;
+    for line in os_release.lines() {
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim_matches('"').to_string();
+            match key {
+                "PRETTY_NAME" => return Some(value),
+                "NAME" => fallback = Some(value),
+                _ => {}
+            }
+        }
+    }
+    fallback
+}
+
+// Total and available memory in MiB. Only the total is known on macOS.
+pub fn memory_mb() -> Option<(u64, Option<u64>)> {
+    if cfg!(target_os = "macos") {
+        let bytes: u64 = run_for_stdout("sysctl", &["-n", "hw.memsize"])?.parse().ok()?;
+        return Some((bytes / 1024 / 1024, None));
+    }
+
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+            .map(|kb| kb / 1024)
+    };
+    Some((field("MemTotal:")?, field("MemAvailable:")))
+}
+
+fn list_gsettings_schemas() -> Vec<String> {
+    run_for_stdout("gsettings", &["list-schemas"])
+        .map(|out| out.lines().map(|line| line.trim().to_string()).collect())
+        .unwrap_or_default()
+}
+
+fn run_for_stdout(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Checks a catalog entry's declared platform support (and the gsettings schema its
+// command targets, when we know which schemas are installed) against this machine.
+pub fn is_supported(platform: &PlatformInfo, support: Option<&PlatformSupport>, command: &str) -> bool {
+    if let Some(schema) = gsettings_schema(command) {
+        if !platform.schemas.is_empty() && !platform.schemas.iter().any(|s| s == schema) {
+            return false;
+        }
+    }
+
+    let Some(support) = support else {
+        return true;
+    };
+
+    if !support.session_types.is_empty()
+        && !support.session_types.iter().any(|s| s.eq_ignore_ascii_case(&platform.session_type))
+    {
+        return false;
+    }
+
+    if let Some(version) = platform.desktop_version.as_deref() {
+        if let Some(min) = support.min_desktop_version.as_deref() {
+            if compare_versions(version, min) == std::cmp::Ordering::Less {
+                return false;
+            }
+        }
+        if let Some(max) = support.max_desktop_version.as_deref() {
+            if compare_versions(version, max) == std::cmp::Ordering::Greater {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn gsettings_schema(command: &str) -> Option<&str> {
+    let mut parts = command.split_whitespace();
+    if parts.next()? != "gsettings" || parts.next()? != "set" {
+        return None;
+    }
+    parts.next()
+}
+
+fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    let parse = |v: &str| -> Vec<u32> {
+        v.split('.')
+            .map(|part| part.chars().take_while(|c| c.is_ascii_digit()).collect::<String>())
+            .map(|digits| digits.parse().unwrap_or(0))
+            .collect()
+    };
+    let (a, b) = (parse(a), parse(b));
+    for i in 0..a.len().max(b.len()) {
+        let ord = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if ord != std::cmp::Ordering::Equal {
+            return ord;
+        }
+    }
+    std::cmp::Ordering::Equal
+}
@@ -5,7 +5,7 @@ use serde_json::Value;
 use tauri::State;
 use once_cell::sync::Lazy;
 use crate::state::{EncryptionClientInstance, GenerateState};
-use crate::models::{AppConfig, Setting, UpdateJSONPreferencesRequest, DefaultValue};
+use crate::models::{AppConfig, Setting, UpdateJSONPreferencesRequest, DefaultValue, PlatformInfo};
 use crate::encryption::DecryptData;
 
 pub static OLLAMA_BASE_URL: Lazy<String> = Lazy::new(|| {
@@ -104,7 +104,10 @@ pub async fn fetch_preferences_impl(
     };
 
     println!("[fetch_preferences] Filtering JSON for environment: {}", env);
-    let filtered_json_str = match filter_json_by_env(&flattened, env) {
+    let platform = state.get_platform().await;
+    let filtered_json_str = match filter_json_by_env(&flattened, env)
+        .and_then(|fj| if platform.env == env { filter_unsupported_settings(&fj, &platform) } else { Ok(fj) })
+    {
         Ok(fj) => {
             println!("[fetch_preferences] Successfully filtered JSON.");
             fj
@@ -139,30 +142,60 @@ pub fn filter_json_by_env(json_str: &str, env: &str) -> Result<String, serde_jso
     serde_json::to_string_pretty(&data)
 }
 
-pub fn find_best_match(prompt: &str, json_str: &str) -> Option<String> {
-    let parsed_json: Value = serde_json::from_str(json_str).ok()?;
-    let map = parsed_json.as_object()?;
-
-    let prompt_tokens = preprocess_text(prompt);
+pub fn filter_unsupported_settings(json_str: &str, platform: &PlatformInfo) -> Result<String, serde_json::Error> {
+    let mut data: Value = serde_json::from_str(json_str)?;
 
-    let mut best_match: Option<String> = None;
-    let mut highest_score = 0.0;
+    if let Value::Object(ref mut categories) = data {
+        for (key, setting_value) in categories.iter_mut() {
+            let Ok(setting) = serde_json::from_value::<Setting>(setting_value.clone()) else {
+                continue;
+            };
+            let command = command_for_env(&setting, &platform.env);
+            if command.is_empty() || crate::platform::is_supported(platform, setting.platforms.as_ref(), &command) {
+                continue;
+            }
 
-    for (key, setting_val) in map {
-        if let Value::Object(obj) = setting_val {
-            if let Some(Value::Object(commands_obj)) = obj.get("commands") {
-                if !commands_obj.is_empty() {
-                    let key_tokens = preprocess_text(key);
-                    let score = cosine_similarity(&prompt_tokens, &key_tokens);
-                    if score > highest_score {
-                        highest_score = score;
-                        best_match = Some(key.clone());
-                    }
-                }
+            println!("[filter] '{}' is not supported on {} ({}), hiding its commands", key, platform.env, platform.session_type);
+            if let Value::Object(ref mut setting_obj) = setting_value {
+                setting_obj.insert("commands".to_string(), Value::Object(serde_json::Map::new()));
             }
         }
     }
-    best_match
+    serde_json::to_string_pretty(&data)
+}
+
+pub fn command_for_env(setting: &Setting, env: &str) -> String {
+    let command = match env {
+        s if s.contains("gnome") => &setting.commands.gnome,
+        "macos" => &setting.commands.macos,
+        "windows" => &setting.commands.windows,
+        _ => "",
+    };
+    command.trim().to_string()
+}
+
+// Splits a full command string into the catalog command it starts with and the value
+// after it. Matching on the catalog rather than on whitespace keeps values with spaces
+// in them ("Cantarell 13") whole.
+pub fn split_command(config: &AppConfig, env: &str, command: &str) -> Option<(String, String)> {
+    let command = command.trim();
+    config
+        .values()
+        .map(|setting| command_for_env(setting, env))
+        .filter(|base| !base.is_empty())
+        .filter_map(|base| {
+            let value = command.strip_prefix(base.as_str())?;
+            let value = value.strip_prefix(char::is_whitespace)?.trim();
+            (!value.is_empty()).then(|| (base.clone(), value.to_string()))
+        })
+        .max_by_key(|(base, _)| base.len())
+}
+
+pub fn find_setting_for_command(full_json: &str, env: &str, base_command: &str) -> Option<(String, Setting)> {
+    let config: AppConfig = serde_json::from_str(full_json).ok()?;
+    config
+        .into_iter()
+        .find(|(_, setting)| command_for_env(setting, env) == base_command.trim())
 }
 
 pub async fn update_json_current_value(
@@ -179,9 +212,10 @@ pub async fn update_json_current_value(
     let mut config: AppConfig = serde_json::from_str(&current_full_json)
         .map_err(|e| format!("Could not parse preferences into AppConfig: {}", e))?;
 
+    let platform = state.get_platform().await;
     let mut found_match = false;
     for (_key, setting) in config.iter_mut() {
-        if setting.commands.gnome.trim() == base_command.trim() {
+        if command_for_env(setting, &platform.env) == base_command.trim() {
             let new_val: DefaultValue = parse_new_value(new_value_str, &setting.current);
             setting.current = new_val;
             found_match = true;
@@ -198,6 +232,11 @@ pub async fn update_json_current_value(
     let updated_full_json = serde_json::to_string_pretty(&config)
         .map_err(|e| format!("Failed to serialize updated JSON: {}", e))?;
 
+    let newly_filtered = filter_json_by_env(&updated_full_json, &platform.env)
+        .and_then(|fj| filter_unsupported_settings(&fj, &platform))
+        .map_err(|e| format!("Failed to filter updated JSON: {}", e))?;
+    state.update_jsons(&updated_full_json, &newly_filtered).await;
+
     let update_payload = UpdateJSONPreferencesRequest {
         username: username.to_string(),
         preferences: config,
@@ -224,16 +263,10 @@ pub async fn update_json_current_value(
     }
 
     println!("Successfully updated preferences on the server.");
-
-    let platform_info = state.get_platform_info().await;
-    let newly_filtered = filter_json_by_env(&updated_full_json, &platform_info)
-        .map_err(|e| format!("Failed to filter updated JSON: {}", e))?;
-
-    state.update_jsons(&updated_full_json, &newly_filtered).await;
     Ok(())
 }
 
-fn parse_new_value(new_value_str: &str, default_val: &DefaultValue) -> DefaultValue {
+pub fn parse_new_value(new_value_str: &str, default_val: &DefaultValue) -> DefaultValue {
     match default_val {
         DefaultValue::Bool(_) => {
             if let Ok(b) = new_value_str.parse::<bool>() {
@@ -253,19 +286,24 @@ fn parse_new_value(new_value_str: &str, default_val: &DefaultValue) -> DefaultVa
     }
 }
 
+// Built from the filtered catalog, so settings hidden as unsupported on this platform
+// can't be run either.
 pub async fn gather_valid_commands_for_env(
     state: &crate::state::GenerateState,
     env: &str,
 ) -> Result<std::collections::HashSet<String>, String> {
-    let full_json = state.get_full_json().await;
-    if full_json.is_empty() {
+    valid_commands_for_env(&state.get_filtered_json().await, env)
+}
+
+pub fn valid_commands_for_env(catalog_json: &str, env: &str) -> Result<std::collections::HashSet<String>, String> {
+    if catalog_json.is_empty() {
         return Ok(std::collections::HashSet::new());
     }
 
-    let parsed: Value = match serde_json::from_str(&full_json) {
+    let parsed: Value = match serde_json::from_str(catalog_json) {
         Ok(val) => val,
         Err(e) => {
-            return Err(format!("Unable to parse full JSON in gather_valid_commands_for_env: {}", e));
+            return Err(format!("Unable to parse catalog JSON in gather_valid_commands_for_env: {}", e));
         }
     };
 
@@ -273,15 +311,9 @@ pub async fn gather_valid_commands_for_env(
     if let Value::Object(map) = parsed {
         for (_, setting_value) in map.iter() {
             if let Ok(setting) = serde_json::from_value::<Setting>(setting_value.clone()) {
-                let env_cmd = match env {
-                    s if s.contains("gnome") => &setting.commands.gnome,
-                    "macos" => &setting.commands.macos,
-                    "windows" => &setting.commands.windows,
-                    _ => "",
-                };
-                let trimmed = env_cmd.trim();
-                if !trimmed.is_empty() {
-                    valid_commands.insert(trimmed.to_string());
+                let env_cmd = command_for_env(&setting, env);
+                if !env_cmd.is_empty() {
+                    valid_commands.insert(env_cmd);
                 }
             }
         }
@@ -289,31 +321,47 @@ pub async fn gather_valid_commands_for_env(
     Ok(valid_commands)
 }
 
-fn preprocess_text(text: &str) -> std::collections::HashSet<String> {
-    let stopwords: std::collections::HashSet<&str> = [
-        "the", "is", "to", "a", "and", "for", "on", "in", "of", "with", 
-        "set", "enable", "disable"
-    ]
-    .iter()
-    .cloned()
-    .collect();
-
-    let stemmer = rust_stemmers::Stemmer::create(rust_stemmers::Algorithm::English);
-
-    text.to_lowercase()
-        .split_whitespace()
-        .filter(|word| !stopwords.contains(*word))
-        .map(|word| stemmer.stem(word).to_string())
-        .collect()
-}
-
-fn cosine_similarity(set1: &std::collections::HashSet<String>, set2: &std::collections::HashSet<String>) -> f64 {
-    let intersection = set1.intersection(set2).count() as f64;
-    let norm1 = set1.len() as f64;
-    let norm2 = set2.len() as f64;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_settings_are_not_valid_commands() {
+        let catalog = include_str!("../json_example.json");
+        let platform = PlatformInfo {
+            env: "gnome".to_string(),
+            session_type: "x11".to_string(),
+            desktop_version: Some("3.30".to_string()),
+            ..Default::default()
+        };
+        let filtered = filter_unsupported_settings(&filter_json_by_env(catalog, "gnome").unwrap(), &platform).unwrap();
+
+        let all = valid_commands_for_env(catalog, "gnome").unwrap();
+        let supported = valid_commands_for_env(&filtered, "gnome").unwrap();
+        let locate_pointer = "gsettings set org.gnome.desktop.interface locate-pointer";
+        assert!(all.contains(locate_pointer));
+        assert!(!supported.contains(locate_pointer));
+        assert!(supported.contains("gsettings set org.gnome.desktop.interface text-scaling-factor"));
+    }
 
-    if norm1 == 0.0 || norm2 == 0.0 {
-        return 0.0;
+    #[tokio::test]
+    async fn gathered_commands_leave_out_settings_the_platform_lacks() {
+        let catalog = include_str!("../json_example.json");
+        let state = crate::state::GenerateState::default();
+        state.set_platform(PlatformInfo {
+            env: "gnome".to_string(),
+            session_type: "x11".to_string(),
+            desktop_version: Some("3.30".to_string()),
+            ..Default::default()
+        });
+        let platform = state.get_platform().await;
+        let filtered = filter_json_by_env(catalog, &platform.env)
+            .and_then(|fj| filter_unsupported_settings(&fj, &platform))
+            .unwrap();
+        state.update_jsons(catalog, &filtered).await;
+
+        let commands = gather_valid_commands_for_env(&state, "gnome").await.unwrap();
+        assert!(!commands.contains("gsettings set org.gnome.desktop.interface locate-pointer"));
+        assert!(commands.contains("gsettings set org.gnome.desktop.interface text-scaling-factor"));
     }
-    intersection / (norm1.sqrt() * norm2.sqrt())
 }
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use reqwest::Client;
+use serde_json::Value;
+use once_cell::sync::Lazy;
+use crate::models::Preset;
+use crate::preferences::SERVER_URL;
+use crate::state::GenerateState;
+
+const BUILTIN_PRESETS: &str = include_str!("../presets.json");
+
+pub static PRESETS_PATH: Lazy<Option<String>> = Lazy::new(|| {
+    env::var("PRESETS_PATH").ok().filter(|path| !path.trim().is_empty())
+});
+
+pub async fn load_presets(state: &GenerateState) -> Vec<Preset> {
+    if let Some(presets) = state.get_presets().await {
+        return presets;
+    }
+    reload_presets(state).await
+}
+
+// Built-in presets are merged with any admin-provided ones (file first, then server);
+// when two share an id the higher version wins.
+pub async fn reload_presets(state: &GenerateState) -> Vec<Preset> {
+    let mut merged: HashMap<String, Preset> = HashMap::new();
+
+    match parse_presets(BUILTIN_PRESETS) {
+        Ok(presets) => merge_presets(&mut merged, presets),
+        Err(e) => println!("[presets] Failed to parse built-in presets: {}", e),
+    }
+
+    if let Some(path) = PRESETS_PATH.as_deref() {
+        match fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|s| parse_presets(&s)) {
+            Ok(presets) => {
+                println!("[presets] Loaded {} preset(s) from {}", presets.len(), path);
+                merge_presets(&mut merged, presets);
+            }
+            Err(e) => println!("[presets] Failed to load presets from {}: {}", path, e),
+        }
+    }
+
+    match fetch_server_presets().await {
+        Ok(presets) => merge_presets(&mut merged, presets),
+        Err(e) => println!("[presets] No presets from server: {}", e),
+    }
+
+    let mut presets: Vec<Preset> = merged.into_values().collect();
+    presets.sort_by(|a, b| a.name.cmp(&b.name));
+    state.set_presets(presets.clone()).await;
+    presets
+}
+
+async fn fetch_server_presets() -> Result<Vec<Preset>, String> {
+    let response = Client::new()
+        .get(format!("{}/presets", *SERVER_URL))
+        .send()
+        .await
+        .map_err(|e| format!("HTTP request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Server responded with status: {}", response.status()));
+    }
+
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read response body: {}", e))?;
+    parse_presets(&body)
+}
+
+fn parse_presets(json_str: &str) -> Result<Vec<Preset>, String> {
+    let mut parsed: Value = serde_json::from_str(json_str)
+        .map_err(|e| format!("Failed to parse presets: {}", e))?;
+    if let Some(inner) = parsed.get_mut("presets") {
+        parsed = inner.take();
+    }
+    serde_json::from_value(parsed).map_err(|e| format!("Failed to parse presets: {}", e))
+}
+
+fn merge_presets(merged: &mut HashMap<String, Preset>, presets: Vec<Preset>) {
+    for preset in presets {
+        match merged.get(&preset.id) {
+            Some(existing) if existing.version >= preset.version => {}
+            _ => {
+                merged.insert(preset.id.clone(), preset);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AppConfig;
+    use crate::preferences::{find_setting_for_command, parse_new_value, split_command, valid_commands_for_env};
+    use crate::profiles;
+
+    const CATALOG: &str = include_str!("../json_example.json");
+    const ENV: &str = "gnome";
+
+    // Follows apply_diffs and the checks execute_command_impl makes before running a command.
+    #[test]
+    fn builtin_presets_apply_to_the_bundled_catalog() {
+        let config: AppConfig = serde_json::from_str(CATALOG).unwrap();
+        let valid_commands = valid_commands_for_env(CATALOG, ENV).unwrap();
+        let presets = parse_presets(BUILTIN_PRESETS).unwrap();
+        assert!(!presets.is_empty());
+
+        for preset in presets {
+            let diffs = profiles::diff_values(&config, ENV, &preset.values);
+            assert!(!diffs.is_empty(), "preset '{}' changes nothing", preset.id);
+
+            for diff in diffs {
+                let (base_command, value) = profiles::diff_command(&config, ENV, &diff)
+                    .unwrap_or_else(|| panic!("preset '{}': no command for '{}'", preset.id, diff.key));
+                assert!(
+                    valid_commands.contains(&base_command),
+                    "preset '{}': '{}' is not an allowed command",
+                    preset.id, base_command
+                );
+
+                let (key, setting) = find_setting_for_command(CATALOG, ENV, &base_command).unwrap();
+                assert_eq!(key, diff.key);
+                assert_eq!(parse_new_value(&value, &setting.current), diff.target, "preset '{}'", preset.id);
+
+                let command = format!("{} {}", base_command, value);
+                assert_eq!(split_command(&config, ENV, &command), Some((base_command, value)));
+            }
+        }
+    }
+}
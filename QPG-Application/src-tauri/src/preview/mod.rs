@@ -0,0 +1,156 @@
+use std::env;
+use once_cell::sync::Lazy;
+use crate::confirmation;
+use crate::locale::Language;
+use crate::models::{ActionPreview, AppConfig, ChangeSource, DefaultValue, SettingAction};
+use crate::retrieval;
+use crate::state::GenerateState;
+
+// On by default: commands from the webview only run once the user has approved the
+// preview they were shown. Turning it off lets any caller run catalog commands.
+pub static REQUIRE_APPROVAL: Lazy<bool> = Lazy::new(|| {
+    !matches!(env::var("REQUIRE_APPROVAL").unwrap_or_default().trim().to_lowercase().as_str(), "0" | "false" | "no")
+});
+
+// Actions for settings that aren't in the catalog are left without a preview; they
+// are refused when executed anyway.
+pub fn attach(actions: &mut [SettingAction], config: &AppConfig, language: Language) {
+    for action in actions {
+        action.preview = preview(action, config, language);
+    }
+}
+
+// Remembers each previewed action under its preview id, so an approval is only good
+// for the action the user was shown.
+pub async fn issue(state: &GenerateState, actions: &[SettingAction]) {
+    for action in actions {
+        if let Some(preview) = &action.preview {
+            state.add_approval(preview.id.clone(), action.clone()).await;
+        }
+    }
+}
+
+pub fn preview(action: &SettingAction, config: &AppConfig, language: Language) -> Option<ActionPreview> {
+    let setting = config.get(&action.setting)?;
+    let display_name = retrieval::display_name(&action.setting, setting, language);
+    let auto_revertible = confirmation::needs_confirmation(setting, &action.value, ChangeSource::Chat);
+
+    let mut effect = describe_effect(&display_name, &setting.current, &action.value, language);
+    if auto_revertible {
+        effect.push(' ');
+        effect.push_str(&language.reverts_unless_kept(*confirmation::CONFIRM_TIMEOUT_SECS));
+    }
+
+    Some(ActionPreview {
+        id: uuid::Uuid::new_v4().to_string(),
+        display_name,
+        old_value: setting.current.clone(),
+        new_value: action.value.clone(),
+        effect,
+        auto_revertible,
+    })
+}
+
+fn describe_effect(name: &str, old: &DefaultValue, new: &DefaultValue, language: Language) -> String {
+    if old == new {
+        return language.already(name, &new.to_string());
+    }
+    match (old, new) {
+        (_, DefaultValue::Bool(true)) => language.turns_on(name),
+        (_, DefaultValue::Bool(false)) => language.turns_off(name),
+        (DefaultValue::Float(old), DefaultValue::Float(new)) if new > old => {
+            language.goes_up(name, &old.to_string(), &new.to_string())
+        }
+        (DefaultValue::Float(old), DefaultValue::Float(new)) => {
+            language.goes_down(name, &old.to_string(), &new.to_string())
+        }
+        _ => language.changes_from(name, &old.to_string(), &new.to_string()),
+    }
+}
+
+// `commands` pairs each command with the preview id it came with, and `approved` holds
+// the ids the user confirmed. Every command needs an approved id that was issued for
+// that exact command. Approvals are used up when checked, so one can't be replayed.
+// With `required` set, the actions as they were issued are returned, in the order of
+// `commands`, so nothing else the webview sent along is trusted.
+pub async fn check_approval(
+    state: &GenerateState,
+    commands: &[(&str, Option<&str>)],
+    approved: &[String],
+    required: bool,
+) -> Result<Option<Vec<SettingAction>>, String> {
+    if !required {
+        return Ok(None);
+    }
+    let refuse = |command: &str| {
+        println!("[preview] Refusing unapproved command: {}", command);
+        Err("This change needs your approval before it can be applied.".to_string())
+    };
+
+    let mut ids = Vec::new();
+    for (command, id) in commands {
+        match id.filter(|id| approved.iter().any(|approved| approved == id)) {
+            Some(id) => ids.push(id.to_string()),
+            None => return refuse(command),
+        }
+    }
+    let Some(issued) = state.take_approvals(&ids).await else {
+        return refuse(commands.first().map(|(command, _)| *command).unwrap_or_default());
+    };
+    match issued.iter().zip(commands).find(|(action, (command, _))| action.command != *command) {
+        Some((_, (command, _))) => refuse(command),
+        None => Ok(Some(issued)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proposed(language: Language) -> SettingAction {
+        let config: AppConfig = serde_json::from_str(include_str!("../json_example.json")).unwrap();
+        let mut actions = vec![crate::tools::action_from_command(
+            &config,
+            "gnome",
+            "gsettings set org.gnome.desktop.interface text-scaling-factor 1.5",
+        ).unwrap()];
+        attach(&mut actions, &config, language);
+        actions.remove(0)
+    }
+
+    #[test]
+    fn previews_describe_the_change() {
+        let action = proposed(Language::English);
+        let preview = action.preview.unwrap();
+        assert_eq!(preview.display_name, "Text size");
+        assert_eq!(preview.old_value, DefaultValue::Float(1.0));
+        assert_eq!(preview.new_value, DefaultValue::Float(1.5));
+        assert!(preview.effect.starts_with("Text size will go up from 1 to 1.5."));
+    }
+
+    #[tokio::test]
+    async fn approvals_only_cover_the_previewed_command_once() {
+        let state = GenerateState::default();
+        let action = proposed(Language::English);
+        let id = action.preview.as_ref().unwrap().id.clone();
+        issue(&state, std::slice::from_ref(&action)).await;
+
+        let command = action.command.as_str();
+        assert!(check_approval(&state, &[(command, Some(&id))], &[], true).await.is_err());
+        assert!(check_approval(&state, &[("gsettings set org.gnome.desktop.interface text-scaling-factor 3.0", Some(&id))], std::slice::from_ref(&id), true).await.is_err());
+
+        let again = proposed(Language::English);
+        let id = again.preview.as_ref().unwrap().id.clone();
+        issue(&state, std::slice::from_ref(&again)).await;
+        let issued = check_approval(&state, &[(command, Some(&id))], std::slice::from_ref(&id), true).await.unwrap().unwrap();
+        assert_eq!(issued[0].setting, "zoom");
+        assert!(check_approval(&state, &[(command, Some(&id))], std::slice::from_ref(&id), true).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn without_approval_nothing_is_checked() {
+        let state = GenerateState::default();
+        let command = "gsettings set org.gnome.desktop.interface text-scaling-factor 1.5";
+        assert!(check_approval(&state, &[(command, None)], &[], false).await.unwrap().is_none());
+    }
+}
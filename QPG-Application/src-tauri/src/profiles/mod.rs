@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use reqwest::Client;
+use serde_json::Value;
+use tauri::{AppHandle, State};
+use crate::encryption::DecryptData;
+use crate::models::{AppConfig, ChangeSource, DefaultValue, Profile, SettingDiff, UpdateJSONProfilesRequest};
+use crate::preferences::{self, SERVER_URL};
+use crate::state::{EncryptionClientInstance, GenerateState};
+
+// Nothing is cached when the fetch fails: saving is only done on top of a loaded
+// set, so a failed load can't end up overwriting the profiles on the server.
+pub async fn load_profiles(
+    username: &str,
+    encryption_instance: &State<'_, EncryptionClientInstance>,
+    state: &State<'_, GenerateState>,
+) -> Result<HashMap<String, Profile>, String> {
+    if let Some(profiles) = state.get_profiles().await {
+        return Ok(profiles);
+    }
+
+    let profiles = fetch_profiles_from_server(username, encryption_instance)
+        .await
+        .map_err(|e| format!("Failed to load profiles: {}", e))?;
+    state.set_profiles(profiles.clone()).await;
+    Ok(profiles)
+}
+
+async fn fetch_profiles_from_server(
+    username: &str,
+    encryption_instance: &State<'_, EncryptionClientInstance>,
+) -> Result<HashMap<String, Profile>, String> {
+    let encryption_client = encryption_instance.0.lock().await;
+    if !encryption_client.status {
+        return Err("encryption client is offline".to_string());
+    }
+
+    let mut url = url::Url::parse(&format!("{}/profiles/{}", *SERVER_URL, username))
+        .map_err(|e| format!("Invalid profiles URL: {}", e))?;
+    url.query_pairs_mut().append_pair("client_id", &encryption_client.client_id);
+
+    let response = Client::new()
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("HTTP request failed: {}", e))?;
+
+    // A user who never saved a profile has none on the server yet.
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(HashMap::new());
+    }
+    if !response.status().is_success() {
+        return Err(format!("Server responded with status: {}", response.status()));
+    }
+
+    let encrypted_body: DecryptData = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+    let decrypted_body = encryption_client.decrypt_data(encrypted_body)?;
+
+    let mut parsed: Value = serde_json::from_str(&decrypted_body)
+        .map_err(|e| format!("Failed to parse profiles: {}", e))?;
+    if let Some(inner) = parsed.get_mut("profiles") {
+        parsed = inner.take();
+    }
+    serde_json::from_value(parsed).map_err(|e| format!("Failed to parse profiles: {}", e))
+}
+
+pub async fn save_profiles(
+    username: &str,
+    profiles: HashMap<String, Profile>,
+    encryption_instance: &State<'_, EncryptionClientInstance>,
+    state: &State<'_, GenerateState>,
+) -> Result<(), String> {
+    if state.get_profiles().await.is_none() {
+        return Err("Profiles have not been loaded, not saving over them".to_string());
+    }
+    state.set_profiles(profiles.clone()).await;
+
+    let encryption_client = encryption_instance.0.lock().await;
+    if !encryption_client.status {
+        println!("[profiles] Encryption client offline; profiles kept locally only.");
+        return Ok(());
+    }
+
+    let update_payload = UpdateJSONProfilesRequest {
+        username: username.to_string(),
+        profiles,
+    };
+    let json_payload = serde_json::to_string(&update_payload)
+        .map_err(|e| format!("Failed to serialize payload: {}", e))?;
+    let encrypted_payload = encryption_client.encrypt_data(&json_payload)?;
+
+    let post_url = format!("{}/profiles/update", *SERVER_URL);
+    let update_resp = Client::new()
+        .post(&post_url)
+        .json(&encrypted_payload)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send profiles to server: {}", e))?;
+
+    if !update_resp.status().is_success() {
+        return Err(format!(
+            "Server failed to update profiles. Status: {}",
+            update_resp.status()
+        ));
+    }
+
+    println!("[profiles] Successfully updated profiles on the server.");
+    Ok(())
+}
+
+pub async fn current_config(
+    app_handle: &AppHandle,
+    encryption_instance: &State<'_, EncryptionClientInstance>,
+    state: &State<'_, GenerateState>,
+) -> Result<AppConfig, String> {
+    if state.get_full_json().await.is_empty() {
+        let username = state.get_username(app_handle).await;
+        let platform_info = state.get_platform_info().await;
+        preferences::fetch_preferences_impl(&username, encryption_instance, &platform_info, state).await?;
+    }
+
+    serde_json::from_str(&state.get_full_json().await)
+        .map_err(|e| format!("Could not parse preferences into AppConfig: {}", e))
+}
+
+pub fn snapshot_values(config: &AppConfig, env: &str) -> HashMap<String, DefaultValue> {
+    config
+        .iter()
+        .filter(|(_, setting)| !preferences::command_for_env(setting, env).is_empty())
+        .map(|(key, setting)| (key.clone(), setting.current.clone()))
+        .collect()
+}
+
+pub fn diff_values(
+    config: &AppConfig,
+    env: &str,
+    values: &HashMap<String, DefaultValue>,
+) -> Vec<SettingDiff> {
+    let mut diffs: Vec<SettingDiff> = values
+        .iter()
+        .filter_map(|(key, target)| {
+            let setting = config.get(key)?;
+            if preferences::command_for_env(setting, env).is_empty() || &setting.current == target {
+                return None;
+            }
+            Some(SettingDiff {
+                key: key.clone(),
+                current: Some(setting.current.clone()),
+                target: target.clone(),
+            })
+        })
+        .collect();
+    diffs.sort_by(|a, b| a.key.cmp(&b.key));
+    diffs
+}
+
+// The command and value that set a setting to its target, kept apart so the value
+// isn't split up on its spaces.
+pub fn diff_command(config: &AppConfig, env: &str, diff: &SettingDiff) -> Option<(String, String)> {
+    let base_command = preferences::command_for_env(config.get(&diff.key)?, env);
+    (!base_command.is_empty()).then(|| (base_command, diff.target.to_string()))
+}
+
+// Runs only the settings that differ, so activating a profile that's already
+// mostly in effect doesn't re-run (and re-upload) every command.
+pub async fn apply_diffs(
+    diffs: Vec<SettingDiff>,
+    config: &AppConfig,
+    env: &str,
+    source: ChangeSource,
+    app_handle: &AppHandle,
+    encryption_instance: &State<'_, EncryptionClientInstance>,
+    state: &State<'_, GenerateState>,
+) -> Result<Vec<SettingDiff>, String> {
+    let mut applied = Vec::new();
+    let mut errors = Vec::new();
+
+    for diff in diffs {
+        let Some((base_command, value)) = diff_command(config, env, &diff) else {
+            continue;
+        };
+
+        match crate::commands::generation::execute_command_impl(
+            base_command,
+            value,
+            true,
+            source,
+            app_handle.clone(),
+            encryption_instance.clone(),
+            state.clone(),
+        ).await {
+            Ok(()) => applied.push(diff),
+            Err(e) => errors.push(format!("{}: {}", diff.key, e)),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(format!("Failed to apply some settings: {}", errors.join("; ")));
+    }
+    Ok(applied)
+}
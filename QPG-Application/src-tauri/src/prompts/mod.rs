@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use crate::models::ModelResponse;
+
+const BUILTIN_TEMPLATES: &[&str] = &[include_str!("default.json")];
+const DEFAULT_TEMPLATE: &str = "default";
+// Template files written for a newer layout are rejected rather than half-read.
+const TEMPLATE_FORMAT: u32 = 1;
+
+const SYSTEM_VARIABLES: &[&str] = &["environment", "catalog", "locale", "language_instructions"];
+const USER_TURN_VARIABLES: &[&str] = &["environment", "best_match", "locale", "prompt"];
+
+// Directory of extra *.json templates. One with the same id as a built-in template
+// replaces it when its version is higher.
+pub static PROMPT_TEMPLATES_DIR: Lazy<Option<String>> = Lazy::new(|| {
+    env::var("PROMPT_TEMPLATES_DIR").ok().filter(|path| !path.trim().is_empty())
+});
+
+// Loaded once at startup (see `load`) so a broken template is reported at launch.
+static TEMPLATES: Lazy<Vec<PromptTemplate>> = Lazy::new(load_templates);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PromptTemplate {
+    pub id: String,
+    pub format: u32,
+    pub version: u32,
+    // Model name prefixes this template is for, e.g. "granite3" or "llama3.2". A
+    // template without families is only used as the default.
+    #[serde(default)]
+    pub model_families: Vec<String>,
+    pub system: String,
+    pub user_turn: String,
+    #[serde(default)]
+    pub examples: Vec<PromptExample>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PromptExample {
+    pub user: String,
+    // A reply in the JSON format, checked against ModelResponse when loading.
+    pub assistant: String,
+}
+
+// Values for the {{variables}} in a template. Unused ones are left out of the output.
+pub struct PromptVariables<'a> {
+    pub environment: &'a str,
+    pub catalog: &'a str,
+    pub best_match: &'a str,
+    pub locale: &'a str,
+    // Templates are written in English; these tell the model which language to reply in.
+    pub language_instructions: &'a str,
+    pub prompt: &'a str,
+}
+
+impl PromptVariables<'_> {
+    fn get(&self, name: &str) -> &str {
+        match name {
+            "environment" => self.environment,
+            "catalog" => self.catalog,
+            "best_match" => self.best_match,
+            "locale" => self.locale,
+            "language_instructions" => self.language_instructions,
+            "prompt" => self.prompt,
+            _ => "",
+        }
+    }
+}
+
+impl PromptTemplate {
+    pub fn system_prompt(&self, variables: &PromptVariables) -> String {
+        let mut prompt = render(&self.system, variables);
+        if !self.examples.is_empty() {
+            prompt.push_str("\nExamples of requests and the replies to them:\n");
+            for example in &self.examples {
+                prompt.push_str(&format!("\nRequest: {}\nReply: {}\n", example.user, example.assistant));
+            }
+        }
+        prompt
+    }
+
+    pub fn user_prompt(&self, variables: &PromptVariables) -> String {
+        render(&self.user_turn, variables)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.format != TEMPLATE_FORMAT {
+            return Err(format!("unsupported format {} (expected {})", self.format, TEMPLATE_FORMAT));
+        }
+        check_variables("system", &self.system, SYSTEM_VARIABLES, &["catalog"])?;
+        check_variables("user_turn", &self.user_turn, USER_TURN_VARIABLES, &["best_match", "prompt"])?;
+        if self.user_turn.matches("{{prompt}}").count() != 1 {
+            return Err("user_turn must use {{prompt}} exactly once".to_string());
+        }
+
+        for (index, example) in self.examples.iter().enumerate() {
+            if example.user.trim().is_empty() {
+                return Err(format!("example {} has no user message", index + 1));
+            }
+            serde_json::from_str::<ModelResponse>(&example.assistant)
+                .map_err(|e| format!("example {} is not a valid reply: {}", index + 1, e))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn load() {
+    Lazy::force(&TEMPLATES);
+}
+
+pub fn template_for(model: &str) -> &'static PromptTemplate {
+    select_template(&TEMPLATES, model).expect("the built-in default prompt template is always loaded")
+}
+
+// The template whose longest model family prefixes `model`, or the default one.
+fn select_template<'a>(templates: &'a [PromptTemplate], model: &str) -> Option<&'a PromptTemplate> {
+    let model = model.to_lowercase();
+    templates
+        .iter()
+        .filter_map(|template| {
+            let family = template
+                .model_families
+                .iter()
+                .filter(|family| model.starts_with(&family.to_lowercase()))
+                .map(String::len)
+                .max()?;
+            Some((family, template))
+        })
+        .max_by_key(|(family, _)| *family)
+        .map(|(_, template)| template)
+        .or_else(|| templates.iter().find(|template| template.id == DEFAULT_TEMPLATE))
+}
+
+// Built-in templates have to be valid. Files from PROMPT_TEMPLATES_DIR that aren't
+// are skipped with the reason, so a typo can't take the chat down.
+fn load_templates() -> Vec<PromptTemplate> {
+    let mut templates: HashMap<String, PromptTemplate> = HashMap::new();
+    for source in BUILTIN_TEMPLATES {
+        let template = parse_template(source).unwrap_or_else(|e| panic!("Invalid built-in prompt template: {}", e));
+        templates.insert(template.id.clone(), template);
+    }
+
+    if let Some(dir) = PROMPT_TEMPLATES_DIR.as_deref() {
+        for template in read_template_dir(Path::new(dir)) {
+            let newer = templates
+                .get(&template.id)
+                .is_none_or(|existing| template.version > existing.version);
+            if newer {
+                templates.insert(template.id.clone(), template);
+            }
+        }
+    }
+
+    let mut templates: Vec<PromptTemplate> = templates.into_values().collect();
+    templates.sort_by(|a, b| a.id.cmp(&b.id));
+    for template in &templates {
+        println!("[prompts] Template '{}' v{} for {:?}", template.id, template.version, template.model_families);
+    }
+    templates
+}
+
+fn read_template_dir(dir: &Path) -> Vec<PromptTemplate> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!("[prompts] Failed to read {}: {}", dir.display(), e);
+            return Vec::new();
+        }
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .filter_map(|path| {
+            let loaded = fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|source| parse_template(&source));
+            match loaded {
+                Ok(template) => Some(template),
+                Err(e) => {
+                    println!("[prompts] Skipping {}: {}", path.display(), e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn parse_template(source: &str) -> Result<PromptTemplate, String> {
+    let template: PromptTemplate = serde_json::from_str(source)
+        .map_err(|e| format!("Failed to parse prompt template: {}", e))?;
+    template
+        .validate()
+        .map_err(|e| format!("Prompt template '{}': {}", template.id, e))?;
+    Ok(template)
+}
+
+fn check_variables(field: &str, text: &str, allowed: &[&str], required: &[&str]) -> Result<(), String> {
+    let used = variable_names(text).map_err(|e| format!("{}: {}", field, e))?;
+    if let Some(unknown) = used.iter().find(|name| !allowed.contains(name)) {
+        return Err(format!("{} uses unknown variable {{{{{}}}}}; it can use {:?}", field, unknown, allowed));
+    }
+    if let Some(missing) = required.iter().find(|name| !used.contains(name)) {
+        return Err(format!("{} must use {{{{{}}}}}", field, missing));
+    }
+    Ok(())
+}
+
+fn variable_names(text: &str) -> Result<Vec<&str>, String> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = after.find("}}").ok_or("unterminated {{")?;
+        names.push(after[..end].trim());
+        rest = &after[end + 2..];
+    }
+    Ok(names)
+}
+
+// Single braces are left alone, so templates can show JSON examples as they are.
+fn render(text: &str, variables: &PromptVariables) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        output.push_str(variables.get(after[..end].trim()));
+        rest = &after[end + 2..];
+    }
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builtin() -> PromptTemplate {
+        parse_template(BUILTIN_TEMPLATES[0]).unwrap()
+    }
+
+    fn for_families(id: &str, families: &[&str]) -> PromptTemplate {
+        PromptTemplate {
+            id: id.to_string(),
+            model_families: families.iter().map(|family| family.to_string()).collect(),
+            ..builtin()
+        }
+    }
+
+    fn variables() -> PromptVariables<'static> {
+        PromptVariables {
+            environment: "gnome",
+            catalog: "{}",
+            best_match: "zoom",
+            locale: "en",
+            language_instructions: "",
+            prompt: "make the text bigger",
+        }
+    }
+
+    #[test]
+    fn the_builtin_template_is_valid() {
+        assert!(builtin().validate().is_ok());
+    }
+
+    #[test]
+    fn unknown_variables_are_rejected() {
+        let template = PromptTemplate { system: "{{catalog}} for {{desktop}}".to_string(), ..builtin() };
+        let error = template.validate().unwrap_err();
+        assert!(error.contains("unknown variable {{desktop}}"), "{}", error);
+    }
+
+    #[test]
+    fn user_turn_needs_the_prompt_once() {
+        let missing = PromptTemplate { user_turn: "{{best_match}}".to_string(), ..builtin() };
+        assert!(missing.validate().unwrap_err().contains("{{prompt}}"));
+
+        let twice = PromptTemplate { user_turn: "{{best_match}} {{prompt}} {{prompt}}".to_string(), ..builtin() };
+        assert!(twice.validate().unwrap_err().contains("exactly once"));
+    }
+
+    #[test]
+    fn examples_must_be_valid_replies() {
+        let template = PromptTemplate {
+            examples: vec![PromptExample { user: "bigger text".to_string(), assistant: "Sure!".to_string() }],
+            ..builtin()
+        };
+        let error = template.validate().unwrap_err();
+        assert!(error.starts_with("example 1 is not a valid reply"), "{}", error);
+    }
+
+    #[test]
+    fn unterminated_variables_are_rejected() {
+        assert_eq!(variable_names("{{catalog}} and {{prompt").unwrap_err(), "unterminated {{");
+        let template = PromptTemplate { system: "{{catalog}} {{locale".to_string(), ..builtin() };
+        assert!(template.validate().unwrap_err().contains("unterminated {{"));
+    }
+
+    #[test]
+    fn variables_are_found_and_filled_in() {
+        assert_eq!(variable_names("{{ best_match }}\n{\"a\": 1} {{prompt}}").unwrap(), vec!["best_match", "prompt"]);
+        assert_eq!(
+            render("{{ best_match }}\n{\"a\": 1} {{prompt}} {{nothing}}", &variables()),
+            "zoom\n{\"a\": 1} make the text bigger "
+        );
+        assert_eq!(render("left {{prompt", &variables()), "left {{prompt");
+    }
+
+    #[test]
+    fn the_longest_matching_family_wins() {
+        let templates = vec![
+            builtin(),
+            for_families("llama", &["llama3"]),
+            for_families("llama32", &["Llama3.2"]),
+            for_families("granite", &["granite3", "granite"]),
+        ];
+        let id = |model: &str| select_template(&templates, model).map(|template| template.id.as_str());
+
+        assert_eq!(id("llama3.2:3b"), Some("llama32"));
+        assert_eq!(id("llama3.1:8b"), Some("llama"));
+        assert_eq!(id("granite3-dense"), Some("granite"));
+        assert_eq!(id("mistral"), Some("default"));
+        assert_eq!(select_template(&templates[1..], "mistral").map(|template| template.id.as_str()), None);
+    }
+}
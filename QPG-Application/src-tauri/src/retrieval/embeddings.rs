@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use crate::llm::LlmBackend;
+use crate::locale::Language;
+use crate::models::SettingMatch;
+use crate::state::GenerateState;
+use super::setting_documents;
+
+const CACHE_FILE: &str = "setting_embeddings.json";
+
+pub static EMBEDDING_MODEL: Lazy<String> = Lazy::new(|| {
+    env::var("EMBEDDING_MODEL").unwrap_or_else(|_| "nomic-embed-text".to_string())
+});
+pub static EMBEDDING_MATCH_THRESHOLD: Lazy<f64> = Lazy::new(|| {
+    env::var("EMBEDDING_MATCH_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0.55)
+});
+
+// Vectors are keyed by language and setting and stored with the text they were made
+// from, so an entry is only re-embedded when its description or synonyms change, and
+// switching languages doesn't throw away the other language's vectors.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmbeddingCache {
+    model: String,
+    entries: HashMap<String, CachedEmbedding>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedEmbedding {
+    text: String,
+    vector: Vec<f32>,
+}
+
+// Returns Ok(None) when the embedding model isn't installed, so the caller can fall
+// back to lexical matching.
+pub async fn rank_settings(
+    app_handle: Option<&AppHandle>,
+    llm: &dyn LlmBackend,
+    state: &GenerateState,
+    prompt: &str,
+    json_str: &str,
+    language: Language,
+    top_k: usize,
+) -> Result<Option<Vec<SettingMatch>>, String> {
+    if !model_installed(llm).await {
+        return Ok(None);
+    }
+
+    let texts = setting_documents(json_str, language);
+    if texts.is_empty() {
+        return Ok(Some(Vec::new()));
+    }
+    let cache = refresh_cache(app_handle, llm, state, &texts, language).await?;
+
+    let prompt_vector = embed(llm, vec![prompt.to_string()])
+        .await?
+        .pop()
+        .ok_or("Embedding model returned no vector for the prompt")?;
+
+    let mut matches: Vec<SettingMatch> = texts
+        .keys()
+        .filter_map(|key| {
+            let entry = cache.entries.get(&entry_key(key, language))?;
+            Some(SettingMatch {
+                setting: key.clone(),
+                score: cosine_similarity(&prompt_vector, &entry.vector),
+            })
+        })
+        .filter(|m| m.score > 0.0)
+        .collect();
+
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.setting.cmp(&b.setting)));
+    matches.truncate(top_k);
+    Ok(Some(matches))
+}
+
+async fn model_installed(llm: &dyn LlmBackend) -> bool {
+    match llm.list_models().await {
+        Ok(models) => models.iter().any(|model| {
+            *model == *EMBEDDING_MODEL || model.starts_with(&format!("{}:", *EMBEDDING_MODEL))
+        }),
+        Err(e) => {
+            println!("[embeddings] Could not list local models: {}", e);
+            false
+        }
+    }
+}
+
+async fn embed(llm: &dyn LlmBackend, input: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+    llm.embed(&EMBEDDING_MODEL, input).await
+}
+
+async fn refresh_cache(
+    app_handle: Option<&AppHandle>,
+    llm: &dyn LlmBackend,
+    state: &GenerateState,
+    texts: &BTreeMap<String, String>,
+    language: Language,
+) -> Result<EmbeddingCache, String> {
+    let mut cache = match state.get_embedding_cache().await {
+        Some(cache) => cache,
+        None => load_cache(app_handle),
+    };
+    if cache.model != *EMBEDDING_MODEL {
+        cache = EmbeddingCache {
+            model: EMBEDDING_MODEL.to_string(),
+            entries: HashMap::new(),
+        };
+    }
+
+    let stale: Vec<(String, &String)> = texts
+        .iter()
+        .map(|(key, text)| (entry_key(key, language), text))
+        .filter(|(key, text)| cache.entries.get(key).is_none_or(|entry| entry.text != **text))
+        .collect();
+
+    if !stale.is_empty() {
+        println!("[embeddings] Embedding {} setting description(s) with {}", stale.len(), *EMBEDDING_MODEL);
+        let vectors = embed(llm, stale.iter().map(|(_, text)| text.to_string()).collect()).await?;
+        if vectors.len() != stale.len() {
+            return Err(format!("Expected {} embeddings, got {}", stale.len(), vectors.len()));
+        }
+        for ((key, text), vector) in stale.into_iter().zip(vectors) {
+            cache.entries.insert(key, CachedEmbedding { text: text.clone(), vector });
+        }
+        save_cache(app_handle, &cache);
+    }
+
+    state.set_embedding_cache(cache.clone()).await;
+    Ok(cache)
+}
+
+fn entry_key(setting: &str, language: Language) -> String {
+    format!("{}:{}", language.code(), setting)
+}
+
+fn cache_path(app_handle: Option<&AppHandle>) -> Option<PathBuf> {
+    app_handle?
+        .path()
+        .app_cache_dir()
+        .ok()
+        .map(|dir| dir.join(CACHE_FILE))
+}
+
+fn load_cache(app_handle: Option<&AppHandle>) -> EmbeddingCache {
+    cache_path(app_handle)
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(app_handle: Option<&AppHandle>, cache: &EmbeddingCache) {
+    let Some(path) = cache_path(app_handle) else {
+        return;
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, serde_json::to_string(cache).unwrap_or_default()));
+    if let Err(e) = result {
+        println!("[embeddings] Failed to write {}: {}", path.display(), e);
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| (*x as f64) * (*y as f64)).sum();
+    let norm_a: f64 = a.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+    let norm_b: f64 = b.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
@@ -0,0 +1,94 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use strsim::jaro_winkler;
+use tfidf::tfidf::{Term, TfIdf};
+use crate::locale::Language;
+use crate::models::SettingMatch;
+
+// Below this Jaro-Winkler similarity two words are treated as different words rather
+// than a misspelling of one another.
+const FUZZY_THRESHOLD: f64 = 0.85;
+const FUZZY_MIN_LEN: usize = 4;
+
+// Ranks catalog documents (key, description and synonyms) against the prompt by the
+// cosine of their TF-IDF vectors, so rare, specific words decide the match. A
+// misspelt prompt word counts as its closest catalog word, scaled by how close it is.
+pub fn rank(prompt: &str, documents: &BTreeMap<String, String>, language: Language, top_k: usize) -> Vec<SettingMatch> {
+    let doc_tokens: Vec<Vec<String>> = documents.values().map(|text| tokenize(text, language)).collect();
+
+    let joined: Vec<String> = doc_tokens.iter().map(|tokens| tokens.join(" ")).collect();
+    let mut tfidf = TfIdf::new();
+    for doc in &joined {
+        tfidf.add(doc);
+    }
+    let idf = |term: &str| tfidf.idf(&Term(term)) as f64;
+
+    let vocabulary: BTreeSet<&str> = doc_tokens.iter().flatten().map(String::as_str).collect();
+    let mut query: HashMap<&str, f64> = HashMap::new();
+    for word in tokenize(prompt, language) {
+        let closest = vocabulary
+            .iter()
+            .map(|term| (*term, similarity(&word, term)))
+            .filter(|(_, score)| *score > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((term, score)) = closest {
+            *query.entry(term).or_default() += score * idf(term);
+        }
+    }
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let mut matches: Vec<SettingMatch> = documents
+        .keys()
+        .zip(&doc_tokens)
+        .map(|(key, tokens)| {
+            let mut weights: HashMap<&str, f64> = HashMap::new();
+            for token in tokens {
+                *weights.entry(token.as_str()).or_default() += 1.0;
+            }
+            for (term, weight) in weights.iter_mut() {
+                *weight *= idf(term);
+            }
+            SettingMatch { setting: key.clone(), score: cosine_similarity(&query, &weights) }
+        })
+        .filter(|m| m.score > 0.0)
+        .collect();
+
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.setting.cmp(&b.setting)));
+    matches.truncate(top_k);
+    matches
+}
+
+fn cosine_similarity(a: &HashMap<&str, f64>, b: &HashMap<&str, f64>) -> f64 {
+    let dot: f64 = a.iter().filter_map(|(term, x)| b.get(term).map(|y| x * y)).sum();
+    let norm_a = a.values().map(|x| x * x).sum::<f64>().sqrt();
+    let norm_b = b.values().map(|x| x * x).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+fn similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    if a.len() < FUZZY_MIN_LEN || b.len() < FUZZY_MIN_LEN {
+        return 0.0;
+    }
+    let score = jaro_winkler(a, b);
+    if score >= FUZZY_THRESHOLD { score } else { 0.0 }
+}
+
+// Splits on anything that isn't a letter or digit (keys are snake_case, prompts carry
+// punctuation), drops the language's stopwords and stems what's left.
+pub fn tokenize(text: &str, language: Language) -> Vec<String> {
+    let stemmer = language.stemmer();
+    let stopwords = language.stopwords();
+
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !stopwords.contains(word))
+        .map(|word| stemmer.stem(word).to_string())
+        .collect()
+}
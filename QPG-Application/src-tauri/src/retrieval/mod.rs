@@ -0,0 +1,186 @@
+pub mod embeddings;
+pub mod lexical;
+
+use std::collections::BTreeMap;
+use std::env;
+use once_cell::sync::Lazy;
+use tauri::AppHandle;
+use crate::llm::LlmBackend;
+use crate::locale::Language;
+use crate::models::{AppConfig, Setting, SettingMatch, SettingText};
+use crate::state::GenerateState;
+
+const DEFAULT_CATALOG: &str = include_str!("../json_example.json");
+
+pub static MATCH_THRESHOLD: Lazy<f64> = Lazy::new(|| {
+    env::var("MATCH_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0.25)
+});
+pub static MATCH_TOP_K: Lazy<usize> = Lazy::new(|| {
+    env::var("MATCH_TOP_K")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|k| *k > 0)
+        .unwrap_or(3)
+});
+
+pub struct Ranking {
+    pub matches: Vec<SettingMatch>,
+    pub confident: bool,
+}
+
+// Embedding similarity when the embedding model is installed, otherwise the lexical
+// matcher. Each has its own threshold since their scores aren't comparable. Without
+// an app handle the embedding cache is only kept in memory.
+pub async fn rank(
+    app_handle: Option<&AppHandle>,
+    llm: &dyn LlmBackend,
+    state: &GenerateState,
+    prompt: &str,
+    json_str: &str,
+    language: Language,
+) -> Ranking {
+    match embeddings::rank_settings(app_handle, llm, state, prompt, json_str, language, *MATCH_TOP_K).await {
+        Ok(Some(matches)) => {
+            let confident = matches.first().is_some_and(|m| m.score >= *embeddings::EMBEDDING_MATCH_THRESHOLD);
+            return Ranking { matches, confident };
+        }
+        Ok(None) => {}
+        Err(e) => println!("[retrieval] Embedding ranking failed, using lexical matching: {}", e),
+    }
+
+    let matches = rank_lexical(prompt, json_str, language, *MATCH_TOP_K);
+    let confident = matches.first().is_some_and(|m| m.score >= *MATCH_THRESHOLD);
+    Ranking { matches, confident }
+}
+
+// Scores every setting that has commands against the prompt and returns the best
+// `top_k`, highest first. Settings that share nothing with the prompt are left out.
+pub fn rank_lexical(prompt: &str, json_str: &str, language: Language, top_k: usize) -> Vec<SettingMatch> {
+    lexical::rank(prompt, &setting_documents(json_str, language), language, top_k)
+}
+
+// Catalogs from the server may not carry descriptions or translations, so those fall
+// back to the bundled catalog's text for the same key, and then to English.
+pub fn setting_documents(json_str: &str, language: Language) -> BTreeMap<String, String> {
+    let config: AppConfig = serde_json::from_str(json_str).unwrap_or_default();
+    let defaults: AppConfig = serde_json::from_str(DEFAULT_CATALOG).unwrap_or_default();
+
+    config
+        .iter()
+        .filter(|(_, setting)| has_commands(setting))
+        .map(|(key, setting)| (key.clone(), describe(key, setting, defaults.get(key), language)))
+        .collect()
+}
+
+fn has_commands(setting: &Setting) -> bool {
+    let commands = &setting.commands;
+    [&commands.windows, &commands.macos, &commands.gnome]
+        .iter()
+        .any(|command| !command.trim().is_empty())
+}
+
+fn describe(key: &str, setting: &Setting, fallback: Option<&Setting>, language: Language) -> String {
+    describe_as(key.replace('_', " "), setting, fallback, language)
+}
+
+// The display name with the description and synonyms, for places where the model
+// reads about a setting rather than the matcher.
+pub fn describe_setting(key: &str, setting: &Setting, language: Language) -> String {
+    let defaults: AppConfig = serde_json::from_str(DEFAULT_CATALOG).unwrap_or_default();
+    describe_as(display_name(key, setting, language), setting, defaults.get(key), language)
+}
+
+fn describe_as(mut text: String, setting: &Setting, fallback: Option<&Setting>, language: Language) -> String {
+    let sources: Vec<SettingText> = [language, Language::English]
+        .into_iter()
+        .flat_map(|lang| [Some(setting), fallback].into_iter().flatten().map(move |s| text_for(s, lang)))
+        .collect();
+    let description = sources.iter().find_map(|text| text.description.as_ref());
+    let synonyms = sources
+        .iter()
+        .map(|text| text.synonyms.as_slice())
+        .find(|synonyms| !synonyms.is_empty())
+        .unwrap_or_default();
+
+    if let Some(description) = description {
+        text.push_str(": ");
+        text.push_str(description);
+    }
+    if !synonyms.is_empty() {
+        text.push(' ');
+        text.push_str(language.also_known_as());
+        text.push_str(": ");
+        text.push_str(&synonyms.join(", "));
+    }
+    text
+}
+
+// The catalog's name for the setting in `language`, with the same fallbacks as the
+// descriptions, and finally the key itself.
+pub fn display_name(key: &str, setting: &Setting, language: Language) -> String {
+    let defaults: AppConfig = serde_json::from_str(DEFAULT_CATALOG).unwrap_or_default();
+    let name = [language, Language::English]
+        .into_iter()
+        .flat_map(|lang| [Some(setting), defaults.get(key)].into_iter().flatten().map(move |s| text_for(s, lang)))
+        .find_map(|text| text.display_name);
+    name.unwrap_or_else(|| {
+        let name = key.replace('_', " ");
+        let mut chars = name.chars();
+        chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or(name)
+    })
+}
+
+fn text_for(setting: &Setting, language: Language) -> SettingText {
+    match language {
+        Language::English => SettingText {
+            display_name: setting.display_name.clone(),
+            description: setting.description.clone(),
+            synonyms: setting.synonyms.clone(),
+        },
+        _ => setting.translations.get(language.code()).cloned().unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::EvalCase;
+    use crate::preferences::filter_json_by_env;
+
+    // Floors for the lexical matcher on the eval corpus; raise them as matching improves.
+    const MIN_TOP_1: f64 = 0.9;
+    const MIN_TOP_K: f64 = 1.0;
+    const TOP_K: usize = 3;
+
+    #[test]
+    fn lexical_ranking_finds_the_corpus_settings() {
+        let cases: Vec<EvalCase> = serde_json::from_str(include_str!("../../eval/corpus.json")).unwrap();
+        let filtered_json = filter_json_by_env(include_str!("../json_example.json"), "gnome").unwrap();
+
+        let mut top_1 = 0;
+        let mut top_k = 0;
+        for case in &cases {
+            let language = case.locale.as_deref().map(Language::from_locale).unwrap_or_default();
+            let ranked: Vec<String> = rank_lexical(&case.prompt, &filtered_json, language, TOP_K)
+                .into_iter()
+                .map(|m| m.setting)
+                .collect();
+            if ranked.first() == Some(&case.expected_setting) {
+                top_1 += 1;
+            }
+            if ranked.contains(&case.expected_setting) {
+                top_k += 1;
+            } else {
+                println!("missed {:?}: expected {}, got {:?}", case.prompt, case.expected_setting, ranked);
+            }
+        }
+
+        let total = cases.len() as f64;
+        println!("top-1 {}/{}, top-{} {}/{}", top_1, cases.len(), TOP_K, top_k, cases.len());
+        assert!(top_1 as f64 / total >= MIN_TOP_1, "top-1 accuracy {}/{}", top_1, cases.len());
+        assert!(top_k as f64 / total >= MIN_TOP_K, "top-{} accuracy {}/{}", TOP_K, top_k, cases.len());
+    }
+}
@@ -0,0 +1,156 @@
+use tauri::{AppHandle, Manager};
+use tauri_plugin_shell::ShellExt;
+use crate::locale::Language;
+use crate::models::{AppConfig, DefaultValue, Setting, SettingReading};
+use crate::preferences::{command_for_env, parse_new_value};
+use crate::retrieval;
+use crate::state::GenerateState;
+
+// The catalog only stores the command that sets a value. On GNOME the matching
+// read is the same gsettings call with `get` in place of `set`.
+fn read_command(setting: &Setting, env: &str) -> Option<Vec<String>> {
+    let parts: Vec<String> = command_for_env(setting, env)
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    match parts.as_slice() {
+        [program, verb, rest @ ..] if program == "gsettings" && verb == "set" && !rest.is_empty() => {
+            let mut read = vec![program.clone(), "get".to_string()];
+            read.extend(rest.iter().cloned());
+            Some(read)
+        }
+        _ => None,
+    }
+}
+
+pub async fn read_live_value(app_handle: &AppHandle, setting: &Setting, env: &str) -> Result<DefaultValue, String> {
+    let parts = read_command(setting, env)
+        .ok_or_else(|| format!("Reading live values is not supported on {}", env))?;
+    let (program, args) = parts.split_first().ok_or("Empty read command")?;
+
+    let output = app_handle
+        .shell()
+        .command(program)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run {}: {}", parts.join(" "), e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} exited with code {}",
+            parts.join(" "),
+            output.status.code().unwrap_or_default()
+        ));
+    }
+
+    let stdout = String::from_utf8(output.stdout).unwrap_or_default();
+    Ok(parse_gvariant(&stdout, &setting.current))
+}
+
+// gsettings prints GVariant text: strings are single-quoted and some numbers carry a
+// type prefix such as "uint32 24".
+fn parse_gvariant(raw: &str, like: &DefaultValue) -> DefaultValue {
+    let mut value = raw.trim();
+    for prefix in ["uint32 ", "int32 ", "uint64 ", "int64 ", "double ", "byte "] {
+        if let Some(rest) = value.strip_prefix(prefix) {
+            value = rest;
+        }
+    }
+    let value = value
+        .strip_prefix('\'')
+        .and_then(|v| v.strip_suffix('\''))
+        .unwrap_or(value);
+    parse_new_value(value, like)
+}
+
+pub async fn read_settings(
+    app_handle: &AppHandle,
+    config: &AppConfig,
+    env: &str,
+    keys: &[String],
+) -> Vec<SettingReading> {
+    let mut readings = Vec::new();
+    for key in keys {
+        let Some(setting) = config.get(key) else {
+            continue;
+        };
+
+        let reading = match read_live_value(app_handle, setting, env).await {
+            Ok(live) => {
+                let state = app_handle.state::<GenerateState>();
+                let command = command_for_env(setting, env);
+                if let Some(record) = state.record_external_change(key, &command, &setting.current, &live).await {
+                    println!("[settings] '{}' was changed outside the app to {}", key, record.new_value);
+                }
+                SettingReading {
+                    setting: key.clone(),
+                    differs: !same_value(&live, &setting.current),
+                    live: Some(live),
+                    stored: setting.current.clone(),
+                    error: None,
+                }
+            }
+            Err(e) => {
+                println!("[settings] Could not read '{}': {}", key, e);
+                SettingReading {
+                    setting: key.clone(),
+                    live: None,
+                    stored: setting.current.clone(),
+                    differs: false,
+                    error: Some(e),
+                }
+            }
+        };
+        readings.push(reading);
+    }
+    readings
+}
+
+pub fn same_value(a: &DefaultValue, b: &DefaultValue) -> bool {
+    match (a, b) {
+        (DefaultValue::Float(a), DefaultValue::Float(b)) => (a - b).abs() < 1e-3,
+        _ => a == b,
+    }
+}
+
+pub fn describe_readings(readings: &[SettingReading], config: &AppConfig, language: Language) -> String {
+    readings
+        .iter()
+        .map(|reading| {
+            let name = match config.get(&reading.setting) {
+                Some(setting) => retrieval::display_name(&reading.setting, setting, language),
+                None => reading.setting.replace('_', " "),
+            };
+            let stored = reading.stored.to_string();
+            match &reading.live {
+                Some(live) if reading.differs => language.changed_outside(&name, &live.to_string(), &stored),
+                Some(live) => language.currently(&name, &live.to_string()),
+                None => language.unreadable(&name, &stored),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readings_use_the_localized_name() {
+        let config: AppConfig = serde_json::from_str(include_str!("../json_example.json")).unwrap();
+        let reading = SettingReading {
+            setting: "zoom".to_string(),
+            live: Some(DefaultValue::Float(1.5)),
+            stored: DefaultValue::Float(1.5),
+            differs: false,
+            error: None,
+        };
+        let expected = retrieval::display_name("zoom", &config["zoom"], Language::French);
+        assert_ne!(expected, "zoom");
+        assert_eq!(
+            describe_readings(&[reading], &config, Language::French),
+            Language::French.currently(&expected, "1.5")
+        );
+    }
+}
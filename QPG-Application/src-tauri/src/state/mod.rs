@@ -1,20 +1,75 @@
 use tauri::async_runtime::{RwLock, Mutex};
 use std::collections::HashMap;
-use tokio::sync::OnceCell;
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
+use tokio::sync::{Notify, OnceCell, Semaphore, SemaphorePermit};
 use crate::encryption::EncryptionClient;
-use ollama_rs::Ollama;
+use crate::models::{ChangeRecord, ChangeSource, DefaultValue, ModelStatus, PendingChange, PlatformInfo, Preset, Profile, SettingAction};
+use crate::history::ChangeHistory;
+use crate::retrieval::embeddings::EmbeddingCache;
+use crate::llm::{ChatMessage, LlmBackend};
 
-pub struct OllamaInstance(pub Mutex<Ollama>);
+pub struct LlmInstance(pub Arc<dyn LlmBackend>);
 pub struct EncryptionClientInstance(pub Mutex<EncryptionClient>);
-pub struct ChatIDs(pub Mutex<HashMap<String, bool>>);
+// How many chats can wait on the model at once; further turns queue.
+pub static MAX_CONCURRENT_GENERATIONS: Lazy<usize> = Lazy::new(|| {
+    env::var("MAX_CONCURRENT_GENERATIONS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(2)
+});
+
+// Previews the user hasn't acted on yet; the oldest are dropped past this many.
+const MAX_OPEN_APPROVALS: usize = 64;
+
+// What the model has been sent in each chat, by chat id. Each chat has its own lock,
+// so a slow reply in one chat doesn't hold up the others.
+#[derive(Default)]
+pub struct ChatHistories(pub Mutex<HashMap<String, Arc<Mutex<Vec<ChatMessage>>>>>);
+
+impl ChatHistories {
+    // The chat's history, loaded with `load` the first time the chat is used.
+    pub async fn chat(&self, chat_id: &str, load: impl FnOnce() -> Vec<ChatMessage>) -> Arc<Mutex<Vec<ChatMessage>>> {
+        self.0
+            .lock()
+            .await
+            .entry(chat_id.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(load())))
+            .clone()
+    }
+
+    pub async fn replace(&self, chat_id: &str, messages: Vec<ChatMessage>) {
+        self.0.lock().await.insert(chat_id.to_string(), Arc::new(Mutex::new(messages)));
+    }
+
+    pub async fn remove(&self, chat_id: &str) {
+        self.0.lock().await.remove(chat_id);
+    }
+}
 
 pub struct GenerateState {
     username: OnceCell<String>,
-    platform_info: OnceCell<String>,
+    platform_info: OnceCell<PlatformInfo>,
     full_json_example: RwLock<String>,
     filtered_json_example: RwLock<String>,
-    best_match_json_example: RwLock<String>,
+    best_match_json_examples: RwLock<HashMap<String, String>>,
     startup_apps: RwLock<Vec<String>>,
+    profiles: RwLock<Option<HashMap<String, Profile>>>,
+    active_profile: RwLock<Option<String>>,
+    presets: RwLock<Option<Vec<Preset>>>,
+    history: RwLock<ChangeHistory>,
+    pending_changes: RwLock<HashMap<String, PendingChange>>,
+    approvals: RwLock<Vec<(String, SettingAction)>>,
+    generations: RwLock<HashMap<String, Vec<Arc<Notify>>>>,
+    generation_permits: Semaphore,
+    pulls: RwLock<HashMap<String, Arc<Notify>>>,
+    // A ready model may carry the time its keep-alive runs out.
+    model_status: RwLock<HashMap<String, (ModelStatus, Option<Instant>)>>,
+    embedding_cache: RwLock<Option<EmbeddingCache>>,
+    context_windows: RwLock<HashMap<String, usize>>,
 }
 
 impl Default for GenerateState {
@@ -24,11 +79,23 @@ impl Default for GenerateState {
             platform_info: OnceCell::new(),
             full_json_example: RwLock::new(String::new()),
             filtered_json_example: RwLock::new(String::new()),
-            best_match_json_example: RwLock::new(String::new()),
+            best_match_json_examples: RwLock::new(HashMap::new()),
 	    startup_apps: RwLock::new(vec![
 		"gnome-tweaks".to_string(),
 		"mousepad".to_string(),
 	    ]),
+            profiles: RwLock::new(None),
+            active_profile: RwLock::new(None),
+            presets: RwLock::new(None),
+            history: RwLock::new(ChangeHistory::default()),
+            pending_changes: RwLock::new(HashMap::new()),
+            approvals: RwLock::new(Vec::new()),
+            generations: RwLock::new(HashMap::new()),
+            generation_permits: Semaphore::new(*MAX_CONCURRENT_GENERATIONS),
+            pulls: RwLock::new(HashMap::new()),
+            model_status: RwLock::new(HashMap::new()),
+            embedding_cache: RwLock::new(None),
+            context_windows: RwLock::new(HashMap::new()),
         }
     }
 }
@@ -46,14 +113,30 @@ impl GenerateState {
     }
 
     pub async fn get_platform_info(&self) -> String {
-	self.platform_info
+        self.get_platform().await.env
+    }
+
+    pub async fn get_platform(&self) -> PlatformInfo {
+        self.platform_info
             .get_or_init(|| async {
-		get_platform_info()
+                tauri::async_runtime::spawn_blocking(crate::platform::detect_platform)
+                    .await
+                    .unwrap_or_else(|_| PlatformInfo {
+                        env: crate::platform::detect_env(),
+                        ..Default::default()
+                    })
             })
             .await
             .clone()
     }
 
+    // Fixes the platform instead of detecting it, for running the pipeline outside the app.
+    pub fn set_platform(&self, platform: PlatformInfo) {
+        if self.platform_info.set(platform).is_err() {
+            println!("[state] Platform was already detected, keeping it");
+        }
+    }
+
     pub async fn get_full_json(&self) -> String {
         self.full_json_example.read().await.clone()
     }
@@ -62,17 +145,244 @@ impl GenerateState {
         self.filtered_json_example.read().await.clone()
     }
 
-    pub async fn get_best_match_json(&self) -> String {
-        self.best_match_json_example.read().await.clone()
+    // The snippet of the last confidently matched setting in a chat, for follow-ups.
+    pub async fn get_best_match_json(&self, chat_id: &str) -> String {
+        self.best_match_json_examples.read().await.get(chat_id).cloned().unwrap_or_default()
     }
 
     pub async fn get_startup_apps(&self) -> Vec<String> {
 	self.startup_apps.read().await.clone()
     }
 
-    pub async fn set_best_match_json(&self, value: &str) {
-        let mut writer = self.best_match_json_example.write().await;
-        *writer = value.to_string();
+    pub async fn get_profiles(&self) -> Option<HashMap<String, Profile>> {
+        self.profiles.read().await.clone()
+    }
+
+    pub async fn set_profiles(&self, profiles: HashMap<String, Profile>) {
+        *self.profiles.write().await = Some(profiles);
+    }
+
+    pub async fn get_active_profile(&self) -> Option<String> {
+        self.active_profile.read().await.clone()
+    }
+
+    pub async fn set_active_profile(&self, name: Option<String>) {
+        *self.active_profile.write().await = name;
+    }
+
+    pub async fn get_presets(&self) -> Option<Vec<Preset>> {
+        self.presets.read().await.clone()
+    }
+
+    pub async fn set_presets(&self, presets: Vec<Preset>) {
+        *self.presets.write().await = Some(presets);
+    }
+
+    pub async fn record_change(
+        &self,
+        setting: &str,
+        command: &str,
+        old_value: Option<DefaultValue>,
+        new_value: DefaultValue,
+        source: ChangeSource,
+    ) -> Option<ChangeRecord> {
+        self.history.write().await.record(setting, command, old_value, new_value, source)
+    }
+
+    pub async fn record_external_change(
+        &self,
+        setting: &str,
+        command: &str,
+        stored: &DefaultValue,
+        live: &DefaultValue,
+    ) -> Option<ChangeRecord> {
+        self.history.write().await.record_external(setting, command, stored, live)
+    }
+
+    pub async fn get_history(&self) -> Vec<ChangeRecord> {
+        self.history.read().await.entries()
+    }
+
+    pub async fn take_undo(&self) -> Option<ChangeRecord> {
+        self.history.write().await.take_undo()
+    }
+
+    pub async fn take_redo(&self) -> Option<ChangeRecord> {
+        self.history.write().await.take_redo()
+    }
+
+    pub async fn push_undone(&self, record: ChangeRecord) {
+        self.history.write().await.push_undone(record);
+    }
+
+    pub async fn push_applied(&self, record: ChangeRecord) {
+        self.history.write().await.push_applied(record);
+    }
+
+    pub async fn discard_last_change(&self, setting: &str) -> Option<ChangeRecord> {
+        self.history.write().await.discard_last(setting)
+    }
+
+    pub async fn history_values_at(&self, timestamp: u64) -> std::collections::HashMap<String, DefaultValue> {
+        self.history.read().await.values_at(timestamp)
+    }
+
+    pub async fn add_pending_change(&self, pending: PendingChange) {
+        self.pending_changes.write().await.insert(pending.id.clone(), pending);
+    }
+
+    pub async fn take_pending_change(&self, id: &str) -> Option<PendingChange> {
+        self.pending_changes.write().await.remove(id)
+    }
+
+    pub async fn take_pending_change_for_setting(&self, setting: &str) -> Option<PendingChange> {
+        let mut pending_changes = self.pending_changes.write().await;
+        let id = pending_changes
+            .values()
+            .find(|pending| pending.setting == setting)
+            .map(|pending| pending.id.clone())?;
+        pending_changes.remove(&id)
+    }
+
+    pub async fn get_pending_changes(&self) -> Vec<PendingChange> {
+        self.pending_changes.read().await.values().cloned().collect()
+    }
+
+    pub async fn add_approval(&self, id: String, action: SettingAction) {
+        let mut approvals = self.approvals.write().await;
+        approvals.push((id, action));
+        if approvals.len() > MAX_OPEN_APPROVALS {
+            approvals.remove(0);
+        }
+    }
+
+    // Takes all of `ids` or, if any is unknown, none of them.
+    pub async fn take_approvals(&self, ids: &[String]) -> Option<Vec<SettingAction>> {
+        let mut approvals = self.approvals.write().await;
+        let positions: Vec<usize> = ids
+            .iter()
+            .map(|id| approvals.iter().position(|(issued, _)| issued == id))
+            .collect::<Option<_>>()?;
+        let actions = positions.iter().map(|position| approvals[*position].1.clone()).collect();
+        approvals.retain(|(issued, _)| !ids.contains(issued));
+        Some(actions)
+    }
+
+    // A chat can have a turn running and more queued behind it; each gets its own handle.
+    pub async fn begin_generation(&self, chat_id: &str) -> Arc<Notify> {
+        let cancel = Arc::new(Notify::new());
+        self.generations.write().await.entry(chat_id.to_string()).or_default().push(cancel.clone());
+        cancel
+    }
+
+    pub async fn end_generation(&self, chat_id: &str, cancel: &Arc<Notify>) {
+        let mut generations = self.generations.write().await;
+        if let Some(turns) = generations.get_mut(chat_id) {
+            turns.retain(|turn| !Arc::ptr_eq(turn, cancel));
+            if turns.is_empty() {
+                generations.remove(chat_id);
+            }
+        }
+    }
+
+    // Cancels every running or queued turn in the chat, and nothing in other chats.
+    pub async fn cancel_generation(&self, chat_id: &str) -> bool {
+        match self.generations.write().await.remove(chat_id) {
+            Some(turns) => {
+                turns.iter().for_each(|cancel| cancel.notify_one());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn generation_permit(&self) -> SemaphorePermit<'_> {
+        self.generation_permits
+            .acquire()
+            .await
+            .expect("the generation semaphore is never closed")
+    }
+
+    // Returns None when the model is already being pulled.
+    pub async fn begin_pull(&self, model: &str) -> Option<Arc<Notify>> {
+        let mut pulls = self.pulls.write().await;
+        if pulls.contains_key(model) {
+            return None;
+        }
+        let cancel = Arc::new(Notify::new());
+        pulls.insert(model.to_string(), cancel.clone());
+        Some(cancel)
+    }
+
+    // Only removes the entry for this download; after a cancel the model may already
+    // be downloading again under a new handle.
+    pub async fn end_pull(&self, model: &str, cancel: &Arc<Notify>) {
+        let mut pulls = self.pulls.write().await;
+        if pulls.get(model).is_some_and(|current| Arc::ptr_eq(current, cancel)) {
+            pulls.remove(model);
+        }
+    }
+
+    pub async fn cancel_pull(&self, model: &str) -> bool {
+        match self.pulls.write().await.remove(model) {
+            Some(cancel) => {
+                cancel.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn get_model_status(&self, model: &str) -> ModelStatus {
+        match self.model_status.read().await.get(model) {
+            Some((ModelStatus::Ready, Some(unload_at))) if *unload_at <= Instant::now() => ModelStatus::Unloaded,
+            Some((status, _)) => *status,
+            None => ModelStatus::default(),
+        }
+    }
+
+    pub async fn set_model_status(&self, model: &str, status: ModelStatus) {
+        self.model_status.write().await.insert(model.to_string(), (status, None));
+    }
+
+    // Restarts the keep-alive of a ready model after a request.
+    pub async fn touch_model(&self, model: &str, keep_alive: Duration) {
+        if let Some((ModelStatus::Ready, unload_at)) = self.model_status.write().await.get_mut(model) {
+            *unload_at = Some(Instant::now() + keep_alive);
+        }
+    }
+
+    // Marks a ready model unloaded once its keep-alive has run out. Returns true only
+    // for the call that did so.
+    pub async fn expire_model(&self, model: &str) -> bool {
+        let mut statuses = self.model_status.write().await;
+        match statuses.get_mut(model) {
+            Some((status @ ModelStatus::Ready, Some(unload_at))) if *unload_at <= Instant::now() => {
+                *status = ModelStatus::Unloaded;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub async fn get_embedding_cache(&self) -> Option<EmbeddingCache> {
+        self.embedding_cache.read().await.clone()
+    }
+
+    pub async fn set_embedding_cache(&self, cache: EmbeddingCache) {
+        *self.embedding_cache.write().await = Some(cache);
+    }
+
+    pub async fn get_context_window(&self, model: &str) -> Option<usize> {
+        self.context_windows.read().await.get(model).copied()
+    }
+
+    pub async fn set_context_window(&self, model: &str, window: usize) {
+        self.context_windows.write().await.insert(model.to_string(), window);
+    }
+
+    pub async fn set_best_match_json(&self, chat_id: &str, value: &str) {
+        self.best_match_json_examples.write().await.insert(chat_id.to_string(), value.to_string());
     }
 
     pub async fn update_jsons(&self, new_full: &str, new_filtered: &str) {
@@ -87,46 +397,21 @@ impl GenerateState {
     }
 }
 
-#[tauri::command]
-fn get_platform_info() -> String {
-    #[cfg(target_os = "macos")]
-    {
-        return "macos".into();
-    }
-    #[cfg(target_os = "windows")]
-    {
-        return "windows".into();
-    }
-    #[cfg(target_os = "linux")]
-    {
-        let frontend_env = get_linux_gui();
-        if let Some(env) = frontend_env {
-            if env.to_lowercase().contains("gnome") {
-                return "gnome".into();
-            } else {
-                return format!("linux-{env}").into();
-            }
-        } else {
-            return "linux-unknown".into();
-        }
-    }
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-#[cfg(target_os = "linux")]
-fn get_linux_gui() -> Option<String> {
-    if let Ok(desktop) = std::env::var("XDG_CURRENT_DESKTOP") {
-        if desktop.to_lowercase().contains("gnome") {
-            return Some("gnome".to_string());
-        }
-        return Some(desktop);
-    }
+    #[tokio::test]
+    async fn a_cancelled_pull_leaves_the_next_one_registered() {
+        let state = GenerateState::default();
+        let first = state.begin_pull("llama3").await.unwrap();
+        assert!(state.cancel_pull("llama3").await);
+        let second = state.begin_pull("llama3").await.unwrap();
 
-    if let Ok(session) = std::env::var("DESKTOP_SESSION") {
-        if session.to_lowercase().contains("gnome") {
-            return Some("gnome".to_string());
-        }
-        return Some(session);
-    }
+        state.end_pull("llama3", &first).await;
+        assert!(state.begin_pull("llama3").await.is_none());
 
-    None
+        state.end_pull("llama3", &second).await;
+        assert!(state.begin_pull("llama3").await.is_some());
+    }
 }
@@ -0,0 +1,270 @@
+use serde_json::{json, Value};
+use crate::llm::ToolCall;
+use crate::locale::Language;
+use crate::models::{AppConfig, DefaultValue, Setting, SettingAction};
+use crate::preferences::{command_for_env, parse_new_value, split_command};
+use crate::retrieval;
+
+pub const CLARIFY_TOOL: &str = "ask_clarification";
+pub const QUERY_TOOL: &str = "read_settings";
+
+// One function per catalog setting that has a command for this environment. The
+// setting key is the tool name and its single "value" parameter is typed from the
+// current value, with bounds and allowed options carried over. The description is
+// the catalog's name, description and synonyms in the user's language.
+pub fn tool_definitions(config: &AppConfig, env: &str, language: Language) -> Vec<Value> {
+    let mut keys: Vec<&String> = config
+        .iter()
+        .filter(|(_, setting)| !command_for_env(setting, env).is_empty())
+        .map(|(key, _)| key)
+        .collect();
+    keys.sort();
+    if keys.is_empty() {
+        return Vec::new();
+    }
+
+    let clarify_tool = json!({
+        "type": "function",
+        "function": {
+            "name": CLARIFY_TOOL,
+            "description": "Ask the user which setting they mean when the request could refer to more than one. Use this instead of guessing.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "question": { "type": "string" },
+                    "choices": {
+                        "type": "array",
+                        "items": { "type": "string", "enum": keys },
+                        "minItems": 2,
+                        "maxItems": 4
+                    }
+                },
+                "required": ["question", "choices"]
+            }
+        }
+    });
+
+    let query_tool = json!({
+        "type": "function",
+        "function": {
+            "name": QUERY_TOOL,
+            "description": "Look up the current value of settings when the user asks about them without wanting a change.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "settings": {
+                        "type": "array",
+                        "items": { "type": "string", "enum": keys }
+                    }
+                },
+                "required": ["settings"]
+            }
+        }
+    });
+
+    keys.iter()
+        .map(|key| {
+            let setting = &config[*key];
+            json!({
+                "type": "function",
+                "function": {
+                    "name": key,
+                    "description": format!(
+                        "{}\nCurrent value: {}",
+                        retrieval::describe_setting(key, setting, language),
+                        setting.current
+                    ),
+                    "parameters": {
+                        "type": "object",
+                        "properties": { "value": value_schema(setting) },
+                        "required": ["value"]
+                    }
+                }
+            })
+        })
+        .chain([clarify_tool, query_tool])
+        .collect()
+}
+
+// Returns the question and the offered settings, keeping only choices that are in
+// the catalog for this environment.
+pub fn clarification_from_tool_call(config: &AppConfig, env: &str, call: &ToolCall) -> Option<(String, Vec<String>)> {
+    if call.function.name != CLARIFY_TOOL {
+        return None;
+    }
+    let question = call.function.arguments.get("question")?.as_str()?.to_string();
+    let choices = call
+        .function
+        .arguments
+        .get("choices")?
+        .as_array()?
+        .iter()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect();
+    Some((question, valid_choices(config, env, choices)))
+}
+
+pub fn query_from_tool_call(config: &AppConfig, env: &str, call: &ToolCall) -> Option<Vec<String>> {
+    if call.function.name != QUERY_TOOL {
+        return None;
+    }
+    let settings = call
+        .function
+        .arguments
+        .get("settings")?
+        .as_array()?
+        .iter()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect();
+    Some(valid_choices(config, env, settings))
+}
+
+pub fn valid_choices(config: &AppConfig, env: &str, choices: Vec<String>) -> Vec<String> {
+    let mut valid: Vec<String> = Vec::new();
+    for choice in choices {
+        let known = config
+            .get(&choice)
+            .is_some_and(|setting| !command_for_env(setting, env).is_empty());
+        if known && !valid.contains(&choice) {
+            valid.push(choice);
+        }
+    }
+    valid
+}
+
+fn value_schema(setting: &Setting) -> Value {
+    match &setting.current {
+        DefaultValue::Float(_) => {
+            let mut schema = json!({ "type": "number" });
+            if let Some(lower) = setting.lower_bound {
+                schema["minimum"] = json!(lower);
+            }
+            if let Some(upper) = setting.upper_bound {
+                schema["maximum"] = json!(upper);
+            }
+            schema
+        }
+        DefaultValue::Bool(_) => json!({ "type": "boolean" }),
+        DefaultValue::String(_) => match &setting.options {
+            Some(options) => json!({ "type": "string", "enum": options }),
+            None => json!({ "type": "string" }),
+        },
+    }
+}
+
+pub fn action_from_tool_call(config: &AppConfig, env: &str, call: &ToolCall) -> Result<SettingAction, String> {
+    let key = &call.function.name;
+    let setting = config
+        .get(key)
+        .ok_or_else(|| format!("Model called unknown tool '{}'", key))?;
+    let base_command = command_for_env(setting, env);
+    if base_command.is_empty() {
+        return Err(format!("Setting '{}' has no command for {}", key, env));
+    }
+
+    let raw = call.function.arguments.get("value").unwrap_or(&Value::Null);
+    let value = match (&setting.current, raw) {
+        (DefaultValue::Float(_), Value::Number(n)) => n.as_f64().map(|v| DefaultValue::Float(v as f32)),
+        (DefaultValue::Float(_), Value::String(s)) => s.trim().parse::<f32>().ok().map(DefaultValue::Float),
+        (DefaultValue::Bool(_), Value::Bool(b)) => Some(DefaultValue::Bool(*b)),
+        (DefaultValue::Bool(_), Value::String(s)) => s.trim().parse::<bool>().ok().map(DefaultValue::Bool),
+        (DefaultValue::String(_), Value::String(s)) => Some(DefaultValue::String(s.clone())),
+        _ => None,
+    }
+    .ok_or_else(|| format!("Invalid value {} for '{}'", raw, key))?;
+
+    validate_value(key, setting, &value)?;
+    Ok(SettingAction {
+        setting: key.clone(),
+        command: format!("{} {}", base_command, value),
+        value,
+        preview: None,
+    })
+}
+
+// Legacy replies carry a full command string; map it back onto the catalog so it
+// goes through the same validation as a tool call.
+pub fn action_from_command(config: &AppConfig, env: &str, command: &str) -> Result<SettingAction, String> {
+    let command = command.trim();
+    let (base_command, raw_value) = split_command(config, env, command)
+        .ok_or_else(|| format!("Command '{}' is not in the catalog or has no value", command))?;
+    let (key, setting) = config
+        .iter()
+        .find(|(_, setting)| command_for_env(setting, env) == base_command)
+        .ok_or_else(|| format!("Command '{}' is not in the catalog", base_command))?;
+
+    let value = parse_new_value(&raw_value, &setting.current);
+    validate_value(key, setting, &value)?;
+    Ok(SettingAction {
+        setting: key.clone(),
+        command: command.to_string(),
+        value,
+        preview: None,
+    })
+}
+
+fn validate_value(key: &str, setting: &Setting, value: &DefaultValue) -> Result<(), String> {
+    match value {
+        DefaultValue::Float(v) => {
+            if setting.lower_bound.is_some_and(|lower| *v < lower) || setting.upper_bound.is_some_and(|upper| *v > upper) {
+                return Err(format!(
+                    "Value {} for '{}' is outside {:?}..{:?}",
+                    v, key, setting.lower_bound, setting.upper_bound
+                ));
+            }
+        }
+        DefaultValue::String(s) => {
+            if let Some(options) = &setting.options {
+                if !options.contains(s) {
+                    return Err(format!("Value '{}' for '{}' is not one of {:?}", s, key, options));
+                }
+            }
+        }
+        DefaultValue::Bool(_) => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENV: &str = "gnome";
+
+    fn catalog() -> AppConfig {
+        serde_json::from_str(include_str!("../json_example.json")).unwrap()
+    }
+
+    #[test]
+    fn command_values_keep_their_spaces() {
+        let config = catalog();
+        let base = command_for_env(&config["font_name"], ENV);
+        let action = action_from_command(&config, ENV, &format!("{} Cantarell 13", base)).unwrap();
+        assert_eq!(action.setting, "font_name");
+        assert_eq!(action.value, DefaultValue::String("Cantarell 13".to_string()));
+    }
+
+    #[test]
+    fn commands_outside_the_catalog_are_rejected() {
+        let config = catalog();
+        assert!(action_from_command(&config, ENV, "rm -rf /").is_err());
+        let base = command_for_env(&config["zoom"], ENV);
+        assert!(action_from_command(&config, ENV, &base).is_err());
+        assert!(action_from_command(&config, ENV, &format!("{} 9.0", base)).is_err());
+    }
+
+    #[test]
+    fn tools_are_described_from_the_catalog() {
+        let config = catalog();
+        let tools = tool_definitions(&config, ENV, Language::English);
+        let zoom = tools.iter().find(|tool| tool["function"]["name"] == "zoom").unwrap();
+        let description = zoom["function"]["description"].as_str().unwrap();
+
+        let setting = &config["zoom"];
+        assert!(description.starts_with(setting.display_name.as_deref().unwrap()));
+        assert!(description.contains(setting.description.as_deref().unwrap()));
+        assert!(setting.synonyms.iter().all(|synonym| description.contains(synonym.as_str())));
+    }
+}
@@ -1,6 +1,6 @@
 import { useEffect, useState, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
-import { emit } from "@tauri-apps/api/event";
+import { emit, listen } from "@tauri-apps/api/event";
 
 import "./App.css";
 import { Button, Input, Text, Box, HStack, Flex, Spinner, Code, Image } from "@chakra-ui/react";
@@ -17,8 +17,19 @@ function App() {
   const [showWelcome, setShowWelcome] = useState(true);
   const [isFading, setIsFading] = useState(false);
   const [isLoading, setIsLoading] = useState(false);
-  const [pendingCommand, setPendingCommand] = useState<string | null>(null);
+  const [pendingActions, setPendingActions] = useState<SettingAction[]>([]);
+  const [pendingProfile, setPendingProfile] = useState<string | null>(null);
+  const [selectedActions, setSelectedActions] = useState<boolean[]>([]);
+  const [lowConfidence, setLowConfidence] = useState(false);
   const [online, setOnline] = useState<boolean>(true);
+  // One response can hold several risky changes, each with its own revert deadline.
+  const [pendingChanges, setPendingChanges] = useState<Record<string, PendingChange & { deadline: number }>>({});
+  const [now, setNow] = useState(Date.now());
+  const [streamingText, setStreamingText] = useState("");
+  const [chatsOpen, setChatsOpen] = useState(false);
+  const [savedChats, setSavedChats] = useState<ChatSummary[]>([]);
+  const [modelPull, setModelPull] = useState<ModelPullEvent | null>(null);
+  const [modelStatus, setModelStatus] = useState<Record<string, ModelStatus>>({});
 
   const messagesEndRef = useRef<HTMLDivElement>(null);
 
@@ -28,7 +39,7 @@ function App() {
 
   useEffect(() => {
     scrollToBottom();
-  }, [messages, isLoading]);
+  }, [messages, isLoading, streamingText]);
 
   useEffect(() => {
     const timer = setTimeout(() => {
@@ -56,13 +67,100 @@ function App() {
       done: boolean;
     };
     command?: string;
+    actions: SettingAction[];
+    kind: "action" | "clarify" | "query";
+    choices: string[];
+    readings: SettingReading[];
+    matches: SettingMatch[];
+    low_confidence: boolean;
+    profile: string | null;
   };
 
+  type SettingMatch = {
+    setting: string;
+    score: number;
+  };
+
+  type SettingReading = {
+    setting: string;
+    live?: string | number | boolean;
+    stored: string | number | boolean;
+    differs: boolean;
+    error?: string;
+  };
+
+  type SettingValue = string | number | boolean;
+
+  type ActionPreview = {
+    id: string;
+    display_name: string;
+    old_value: SettingValue;
+    new_value: SettingValue;
+    effect: string;
+    auto_revertible: boolean;
+  };
+
+  type SettingAction = {
+    setting: string;
+    command: string;
+    value: SettingValue;
+    preview?: ActionPreview;
+  };
+
+
+  type GenerateChunk = {
+    chat_id: string;
+    message: string;
+  };
+
+  type ModelStatus = "unloaded" | "loading" | "ready" | "failed";
+
+  type ModelStatusEvent = {
+    model: string;
+    status: ModelStatus;
+    error: string | null;
+  };
+
+  type ModelPullEvent = {
+    model: string;
+    status: string;
+    completed: number | null;
+    total: number | null;
+    done: boolean;
+    error: string | null;
+  };
+
+  type PendingChange = {
+    id: string;
+    setting: string;
+    command: string;
+    old_value: string | number | boolean;
+    new_value: string | number | boolean;
+    source: string;
+    timeout_secs: number;
+  };
+
+  type ChatSummary = {
+    id: string;
+    title: string;
+    model: string;
+    created_at: number;
+    updated_at: number;
+    message_count: number;
+  };
+
+  type ChatSession = {
+    id: string;
+    title: string;
+    model: string;
+    transcript: { sender: string; text: string; timestamp: number }[];
+  };
 
   type MessageType = {
     sender: string;
     text: string;
     timestamp?: Date;
+    choices?: string[];
   };
   
   type ChatMessage = {
@@ -81,43 +179,272 @@ function App() {
     }
   }
 
-  async function generate() {
-    if (isLoading || !prompt) {
+  async function generate(text: string = prompt, setting?: string) {
+    if (isLoading || !text) {
       return;
     }
     if (!selectedModel) {
       alert("Please select a model first");
       return;
     }
-    const userMessage = { sender: "user", text: prompt };
+    const userMessage = { sender: "user", text };
     setMessages([...messages, userMessage]);
     setPrompt("");
     setIsLoading(true);
-    const result = await invoke<GenerateResult>("generate", { 
-      request: { 
-        model: selectedModel, 
-        prompt, 
-        chat_id: chatID 
-      } 
-    });
+    setStreamingText("");
+    try {
+      const result = await invoke<GenerateResult>("generate_stream", { 
+        request: { 
+          model: selectedModel, 
+          prompt: text, 
+          chat_id: chatID,
+          setting,
+          locale: navigator.language
+        } 
+      });
   
-    const botMessage = { 
-      sender: "bot", 
-      text: result.ollama_response.message.content,
-      timestamp: new Date()
-    };
+      const botMessage = { 
+        sender: "bot", 
+        text: result.ollama_response.message.content,
+        timestamp: new Date(),
+        choices: result.kind === "clarify" ? result.choices : undefined
+      };
     
-    setMessages([...messages, userMessage, botMessage]);
+      setMessages([...messages, userMessage, botMessage]);
     
-    if (result.command) {
-      setPendingCommand(result.command);
+      if (result.profile) {
+        setPendingProfile(result.profile);
+      }
+      if (result.actions.length > 0) {
+        setPendingActions(result.actions);
+        setSelectedActions(result.actions.map(() => true));
+        setLowConfidence(result.low_confidence);
+      }
+    } catch (error) {
+      console.log("Generation stopped:", error);
     }
     
+    setStreamingText("");
     setIsLoading(false);
   }
 
+  function closeActions() {
+    setPendingActions([]);
+    setSelectedActions([]);
+  }
+
+  async function executeActions(actions: SettingAction[]) {
+    try {
+      // Only reached from the confirm buttons, so these are the previews the user approved.
+      const approved = actions.flatMap((action) => (action.preview ? [action.preview.id] : []));
+      await invoke("execute_actions", { actions, update: true, approved });
+    } catch (error) {
+      alert(`Error: ${error}`);
+    }
+    closeActions();
+  }
+
+  async function activateProfile(name: string) {
+    try {
+      await invoke("activate_profile", { name });
+    } catch (error) {
+      alert(`Error: ${error}`);
+    }
+    setPendingProfile(null);
+  }
+
+  async function stopGenerating() {
+    try {
+      await invoke("cancel_generation", { chatId: chatID });
+    } catch (error) {
+      console.log("Error cancelling generation:", error);
+    }
+  }
+
+  function removePendingChange(id: string) {
+    setPendingChanges((current) => {
+      const { [id]: _, ...rest } = current;
+      return rest;
+    });
+  }
+
+  async function keepChange(id: string) {
+    try {
+      await invoke("confirm_change", { id });
+    } catch (error) {
+      alert(`Error: ${error}`);
+    }
+    removePendingChange(id);
+  }
+
+  async function revertChange(id: string) {
+    try {
+      await invoke("revert_change", { id });
+    } catch (error) {
+      console.log("Error reverting change:", error);
+    }
+    removePendingChange(id);
+  }
+
+  async function keepAllChanges() {
+    await Promise.all(Object.keys(pendingChanges).map(keepChange));
+  }
+
+  async function revertAllChanges() {
+    await Promise.all(Object.keys(pendingChanges).map(revertChange));
+  }
+
+  useEffect(() => {
+    const unlistenPending = listen<PendingChange>("change-pending-confirmation", (event) => {
+      const deadline = Date.now() + event.payload.timeout_secs * 1000;
+      setPendingChanges((current) => ({ ...current, [event.payload.id]: { ...event.payload, deadline } }));
+      setNow(Date.now());
+    });
+    // Changes can also be kept or reverted from the chat, or time out.
+    const unlistenReverted = listen<PendingChange>("change-reverted", (event) => {
+      removePendingChange(event.payload.id);
+    });
+    const unlistenConfirmed = listen<PendingChange>("change-confirmed", (event) => {
+      removePendingChange(event.payload.id);
+    });
+    const unlistenSuperseded = listen<PendingChange>("change-superseded", (event) => {
+      removePendingChange(event.payload.id);
+    });
+    return () => {
+      unlistenPending.then((unlisten) => unlisten());
+      unlistenReverted.then((unlisten) => unlisten());
+      unlistenConfirmed.then((unlisten) => unlisten());
+      unlistenSuperseded.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlistenStatus = listen<ModelStatusEvent>("model-status", (event) => {
+      setModelStatus((current) => ({ ...current, [event.payload.model]: event.payload.status }));
+      if (event.payload.error) {
+        console.log("Model failed to load:", event.payload.error);
+      }
+    });
+    return () => {
+      unlistenStatus.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlistenPull = listen<ModelPullEvent>("model-pull-progress", (event) => {
+      if (event.payload.done) {
+        setModelPull(null);
+        if (event.payload.error) {
+          console.log("Model download failed:", event.payload.error);
+        }
+        listModels();
+      } else {
+        setModelPull(event.payload);
+      }
+    });
+    return () => {
+      unlistenPull.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlistenChunk = listen<GenerateChunk>("generate-chunk", (event) => {
+      if (event.payload.chat_id === chatID) {
+        setStreamingText(event.payload.message);
+      }
+    });
+    return () => {
+      unlistenChunk.then((unlisten) => unlisten());
+    };
+  }, [chatID]);
+
+  const hasPendingChanges = Object.keys(pendingChanges).length > 0;
+  useEffect(() => {
+    if (!hasPendingChanges) return;
+    const timer = setInterval(() => setNow(Date.now()), 1000);
+    return () => clearInterval(timer);
+  }, [hasPendingChanges]);
+
+  async function showChats() {
+    try {
+      setSavedChats(await invoke<ChatSummary[]>("list_chats"));
+      setChatsOpen(true);
+    } catch (error) {
+      alert(`Error: ${error}`);
+    }
+  }
+
+  async function openChat(chatId: string) {
+    try {
+      const session = await invoke<ChatSession>("open_chat", { chatId });
+      setSelectedModel(session.model);
+      setChatID(session.id);
+      setMessages([
+        { sender: "", text: "" },
+        ...session.transcript.map((entry) => ({
+          sender: entry.sender,
+          text: entry.text,
+          timestamp: new Date(entry.timestamp),
+        })),
+      ]);
+      setIsLoading(false);
+      setChatsOpen(false);
+    } catch (error) {
+      alert(`Error: ${error}`);
+    }
+  }
+
+  async function renameChat(chat: ChatSummary) {
+    const title = window.prompt("Rename chat", chat.title);
+    if (!title) return;
+    try {
+      await invoke("rename_chat", { chatId: chat.id, title });
+      setSavedChats(await invoke<ChatSummary[]>("list_chats"));
+    } catch (error) {
+      alert(`Error: ${error}`);
+    }
+  }
+
+  async function deleteChat(chat: ChatSummary) {
+    if (!window.confirm(`Delete "${chat.title}"? This cannot be undone.`)) return;
+    try {
+      await invoke("delete_chat", { chatId: chat.id });
+      setSavedChats(savedChats.filter((saved) => saved.id !== chat.id));
+      if (chat.id === chatID) {
+        setChatID(selectedModel + Date());
+        setMessages([{ sender: "", text: "" }]);
+      }
+    } catch (error) {
+      alert(`Error: ${error}`);
+    }
+  }
+
+  async function exportChat(chat: ChatSummary) {
+    try {
+      const text = await invoke<string>("export_chat", { chatId: chat.id, format: "markdown" });
+      const url = URL.createObjectURL(new Blob([text], { type: "text/markdown" }));
+      const link = document.createElement("a");
+      link.href = url;
+      link.download = `${chat.title.replace(/[^\w\- ]+/g, "").trim() || "chat"}.md`;
+      link.click();
+      URL.revokeObjectURL(url);
+    } catch (error) {
+      alert(`Error: ${error}`);
+    }
+  }
+
+  async function warmUpModel(model: string) {
+    try {
+      const status: ModelStatus = await invoke("warm_up_model", { model });
+      setModelStatus((current) => ({ ...current, [model]: status }));
+    } catch (error) {
+      console.log("Error loading model:", error);
+    }
+  }
+
   function selectModel(model: string) {
     setSelectedModel(model);
+    warmUpModel(model);
     setChatID(model + Date()); // Date to differentiate when new chats with same model started
     setMessages([{ sender: "", text: "" }]);
     setIsLoading(false);
@@ -164,39 +491,140 @@ function App() {
         height="100vh"
         bg="gray.50"
       >
-        <Modal isOpen={!!pendingCommand} onClose={() => setPendingCommand(null)} isCentered>
+        <Modal isOpen={pendingActions.length > 0} onClose={closeActions} isCentered>
           <ModalOverlay bg="rgba(0, 0, 0, 0.6)" backdropFilter="blur(10px)" />
           <ModalContent borderRadius="xl" boxShadow="lg">
             <ModalHeader fontWeight="bold" fontSize="4xl" textAlign="center">
               Confirm Command Execution
             </ModalHeader>
             <ModalBody display="flex" flexDirection="column" alignItems="center">
-              <Text mb={2} textAlign="center">Would you like to execute this command?</Text>
-              <Code p={2} my={2} display="block" width="100%" textAlign="center">
-                {pendingCommand}
-              </Code>
-              <Text textAlign="center">This will modify your system settings.</Text>
+              <Text mb={2} textAlign="center">
+                {pendingActions.length > 1 ? "Choose which of these changes to make:" : "Would you like to make this change?"}
+              </Text>
+              {pendingActions.map((action, index) => (
+                <Flex key={index} align="center" gap={2} my={1} width="100%">
+                  {pendingActions.length > 1 && (
+                    <input
+                      type="checkbox"
+                      aria-label={`Apply ${action.preview?.display_name ?? action.setting}`}
+                      checked={selectedActions[index] ?? false}
+                      onChange={(e) => {
+                        const next = [...selectedActions];
+                        next[index] = e.target.checked;
+                        setSelectedActions(next);
+                      }}
+                    />
+                  )}
+                  {action.preview ? (
+                    <Box p={2} width="100%" borderWidth="1px" borderRadius="md">
+                      <Text fontWeight="bold">{action.preview.display_name}</Text>
+                      <Text>{String(action.preview.old_value)} → {String(action.preview.new_value)}</Text>
+                      <Text fontSize="sm" color="gray.600">{action.preview.effect}</Text>
+                      <details>
+                        <summary>Command</summary>
+                        <Code p={1} display="block">{action.command}</Code>
+                      </details>
+                    </Box>
+                  ) : (
+                    <Code p={2} display="block" width="100%" textAlign="center">
+                      {action.command}
+                    </Code>
+                  )}
+                </Flex>
+              ))}
+              {lowConfidence && (
+                <Text mb={2} textAlign="center" color="orange.600">
+                  ⚠️ I'm not sure this is the setting you meant. Please check before executing.
+                </Text>
+              )}
+              <Text textAlign="center">This will modify your system settings. If one change fails, the others are undone.</Text>
             </ModalBody>
             <ModalFooter display="flex" justifyContent="center">
-              <Button mr={3} onClick={() => setPendingCommand(null)}>
+              <Button mr={3} onClick={closeActions}>
                 Cancel
               </Button>
-              <Button colorScheme="blue" onClick={async () => {
-                if (pendingCommand) {
-                  try {
-                    await invoke("execute_command", { command: pendingCommand, update: true });
-                  } catch (error) {
-                    alert(`Error: ${error}`);
-                  }
-                  setPendingCommand(null);
-                }
-              }}>
-                Execute
+              {pendingActions.length > 1 && (
+                <Button
+                  mr={3}
+                  disabled={!selectedActions.some((selected) => selected)}
+                  onClick={() => executeActions(pendingActions.filter((_, index) => selectedActions[index]))}
+                >
+                  Execute selected
+                </Button>
+              )}
+              <Button colorScheme="blue" onClick={() => executeActions(pendingActions)}>
+                {pendingActions.length > 1 ? "Execute all" : "Execute"}
               </Button>
             </ModalFooter>
           </ModalContent>
         </Modal>
         
+        <Modal isOpen={!!pendingProfile} onClose={() => setPendingProfile(null)} isCentered>
+          <ModalOverlay bg="rgba(0, 0, 0, 0.6)" backdropFilter="blur(10px)" />
+          <ModalContent borderRadius="xl" boxShadow="lg">
+            <ModalHeader fontWeight="bold" fontSize="4xl" textAlign="center">
+              Switch profile?
+            </ModalHeader>
+            <ModalBody display="flex" flexDirection="column" alignItems="center">
+              <Text textAlign="center">
+                Your settings will change to the ones saved in the {pendingProfile} profile.
+              </Text>
+            </ModalBody>
+            <ModalFooter display="flex" justifyContent="center">
+              <Button mr={3} onClick={() => setPendingProfile(null)}>
+                Cancel
+              </Button>
+              <Button colorScheme="blue" onClick={() => pendingProfile && activateProfile(pendingProfile)}>
+                Switch
+              </Button>
+            </ModalFooter>
+          </ModalContent>
+        </Modal>
+
+        <Modal isOpen={hasPendingChanges} onClose={revertAllChanges} isCentered>
+          <ModalOverlay bg="rgba(0, 0, 0, 0.6)" backdropFilter="blur(10px)" />
+          <ModalContent
+            borderRadius="xl"
+            boxShadow="lg"
+            onKeyDown={(e) => {
+              if (e.key === "Enter") {
+                keepAllChanges();
+              }
+            }}
+          >
+            <ModalHeader fontWeight="bold" fontSize="4xl" textAlign="center">
+              Keep these changes?
+            </ModalHeader>
+            <ModalBody display="flex" flexDirection="column" alignItems="center">
+              {Object.values(pendingChanges).map((change) => (
+                <Flex key={change.id} align="center" gap={2} my={1} width="100%">
+                  <Text flex="1">
+                    {change.setting} changed from {String(change.old_value)} to {String(change.new_value)}.
+                    Reverting in {Math.max(0, Math.ceil((change.deadline - now) / 1000))} seconds.
+                  </Text>
+                  <Button size="sm" onClick={() => revertChange(change.id)}>
+                    Revert
+                  </Button>
+                  <Button size="sm" colorScheme="blue" onClick={() => keepChange(change.id)}>
+                    Keep
+                  </Button>
+                </Flex>
+              ))}
+              <Text textAlign="center" mt={2}>
+                Press Enter to keep all or Escape to revert all.
+              </Text>
+            </ModalBody>
+            <ModalFooter display="flex" justifyContent="center">
+              <Button mr={3} onClick={revertAllChanges}>
+                Revert all
+              </Button>
+              <Button colorScheme="blue" onClick={keepAllChanges} autoFocus>
+                Keep all
+              </Button>
+            </ModalFooter>
+          </ModalContent>
+        </Modal>
+
         <Flex 
           direction="row" 
           justify="space-between" 
@@ -257,6 +685,48 @@ function App() {
             </ModalContent>
           </Modal>
                     
+          <Button 
+            colorScheme="blue" 
+            variant="ghost" 
+            size="sm"
+            onClick={() => showChats()}
+          >
+            <span style={{marginRight: '8px'}}>💬</span>
+            Chats
+          </Button>
+
+          <Modal isOpen={chatsOpen} onClose={() => setChatsOpen(false)} isCentered size="xl">
+            <ModalOverlay bg="rgba(0, 0, 0, 0.3)" backdropFilter="blur(10px)" />
+            <ModalContent borderRadius="lg" shadow="xl">
+              <ModalHeader borderBottomWidth="1px" borderColor="gray.200">
+                <Text fontSize="xl" fontWeight="bold">Saved chats</Text>
+              </ModalHeader>
+              <ModalBody py={6}>
+                {savedChats.length === 0 && (
+                  <Text textAlign="center" color="gray.600">No saved chats yet.</Text>
+                )}
+                {savedChats.map((chat) => (
+                  <Flex key={chat.id} align="center" gap={2} py={2} borderBottomWidth="1px" borderColor="gray.100">
+                    <Box flex="1" cursor="pointer" onClick={() => openChat(chat.id)}>
+                      <Text fontWeight="medium">{chat.title}</Text>
+                      <Text fontSize="xs" color="gray.600">
+                        {chat.model} · {chat.message_count} messages · {new Date(chat.updated_at).toLocaleString()}
+                      </Text>
+                    </Box>
+                    <Button size="xs" variant="ghost" onClick={() => renameChat(chat)}>Rename</Button>
+                    <Button size="xs" variant="ghost" onClick={() => exportChat(chat)}>Export</Button>
+                    <Button size="xs" variant="ghost" colorScheme="red" onClick={() => deleteChat(chat)}>Delete</Button>
+                  </Flex>
+                ))}
+              </ModalBody>
+              <ModalFooter borderTopWidth="1px" borderColor="gray.200">
+                <Button colorScheme="blue" onClick={() => setChatsOpen(false)}>
+                  Close
+                </Button>
+              </ModalFooter>
+            </ModalContent>
+          </Modal>
+
           <Button 
             colorScheme="blue" 
             variant="ghost" 
@@ -289,6 +759,18 @@ function App() {
         
         <Box mb={5}>
           <Text fontSize="md" fontWeight="medium" mb={3} color="gray.600" textAlign="center">Select a model to begin</Text>
+          {modelPull && (
+            <Flex justifyContent="center" alignItems="center" gap={2} mb={3}>
+              <Spinner size="sm" />
+              <Text fontSize="sm" color="gray.600">
+                Downloading {modelPull.model}: {modelPull.status}
+                {modelPull.completed != null && modelPull.total ? ` (${Math.floor((modelPull.completed / modelPull.total) * 100)}%)` : ""}
+              </Text>
+              <Button size="xs" variant="outline" onClick={() => invoke("cancel_model_pull", { model: modelPull.model })}>
+                Cancel
+              </Button>
+            </Flex>
+          )}
           <Flex justifyContent="center" wrap="wrap" gap={2}>
             {models.sort().map((model, index) => (
               <Button 
@@ -346,6 +828,21 @@ function App() {
                 className={`chat-bubble ${message.sender === "user" ? "user-bubble" : "bot-bubble"}`}
               >
                 {message.text}
+                {message.choices && index === messages.filter(msg => msg.sender !== "").length - 1 && (
+                  <Flex wrap="wrap" gap={2} mt={2}>
+                    {message.choices.map((choice) => (
+                      <Button
+                        key={choice}
+                        size="sm"
+                        variant="outline"
+                        disabled={isLoading}
+                        onClick={() => generate(choice.replace(/_/g, " "), choice)}
+                      >
+                        {choice.replace(/_/g, " ")}
+                      </Button>
+                    ))}
+                  </Flex>
+                )}
                 {message.timestamp && (
                   <Text 
                     fontSize="xs" 
@@ -359,10 +856,16 @@ function App() {
               </Box>
             ))}
             
-            {isLoading && (
+            {isLoading && streamingText && (
+              <Box className="chat-bubble bot-bubble">
+                {streamingText}
+              </Box>
+            )}
+
+            {isLoading && !streamingText && (
               <Flex align="center" my={4} className="chat-bubble bot-bubble">
                 <Spinner size="sm" color="blue.500" mr={3}/>
-                <Text>Thinking...</Text>
+                <Text>{modelStatus[selectedModel] === "loading" ? "Loading model..." : "Thinking..."}</Text>
               </Flex>
             )}
             <div ref={messagesEndRef} />
@@ -380,7 +883,13 @@ function App() {
                   generate();
                 }
               }} 
-              placeholder={selectedModel ? "Type your message..." : "Select a model to start chatting"} 
+              placeholder={
+                !selectedModel
+                  ? "Select a model to start chatting"
+                  : modelStatus[selectedModel] === "loading"
+                    ? "Loading model, you can start typing..."
+                    : "Type your message..."
+              } 
               value={prompt} 
               onChange={(e) => setPrompt(e.target.value)}
               variant="outline"
@@ -389,16 +898,28 @@ function App() {
               size="lg"
               disabled={!selectedModel || isLoading}
             />
-            <Button 
-              onClick={generate}
-              disabled={!selectedModel || isLoading || !prompt}
-              colorScheme="blue"
-              borderRadius="full"
-              size="lg"
-              px={6}
-            >
-              Send
-            </Button>
+            {isLoading ? (
+              <Button 
+                onClick={stopGenerating}
+                colorScheme="red"
+                borderRadius="full"
+                size="lg"
+                px={6}
+              >
+                Stop
+              </Button>
+            ) : (
+              <Button 
+                onClick={() => generate()}
+                disabled={!selectedModel || !prompt}
+                colorScheme="blue"
+                borderRadius="full"
+                size="lg"
+                px={6}
+              >
+                Send
+              </Button>
+            )}
           </HStack>
         </Box>
       </Box>